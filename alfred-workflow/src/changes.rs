@@ -0,0 +1,240 @@
+//! What each refresh changed: the rows it added, removed and renamed, and when every row was
+//! first seen, so what appeared recently can be listed.
+//!
+//! Rows are told apart by their table's primary key, so a row whose key changes is removed and
+//! added rather than renamed; keys should not change with the title, eg. the node id of a
+//! GitHub repository. Rows cached by the first refresh of a table, or of a profile, were not
+//! seen appearing and have no `first_seen` time.
+//!
+use crate::errors::Error;
+use crate::search::{SearchFields, SearchQuery};
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, ToSql};
+use serde::Serialize;
+use std::fmt;
+
+/// The column recording when each row of a tracked table was first seen, as seconds since the
+/// epoch, to be added to the tables in a workflow's migrations.
+pub const FIRST_SEEN: &str = "first_seen";
+
+/// A cached resource, a table with a primary key and a `url` column, whose changes are
+/// tracked.
+#[derive(Debug, Clone, Copy)]
+pub struct Tracked {
+    pub resource: &'static str,
+    /// Names a row in summaries, eg. `repo`, made plural by adding an `s`.
+    pub noun: &'static str,
+    /// The column whose change is a rename.
+    pub title: &'static str,
+    /// The column holding the profile of each row, if any.
+    pub profile: Option<&'static str>,
+}
+
+/// How many rows of a resource a refresh added, removed and renamed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Change {
+    pub noun: &'static str,
+    pub added: u64,
+    pub removed: u64,
+    pub renamed: u64,
+}
+
+/// What a refresh changed, eg. `+4 repos, −1 pipeline, 2 monitors renamed`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub changes: Vec<Change>,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let count = |n: u64, noun: &str| format!("{n} {noun}{}", if n == 1 { "" } else { "s" });
+        let parts = self
+            .changes
+            .iter()
+            .flat_map(|c| {
+                [
+                    (c.added > 0).then(|| format!("+{}", count(c.added, c.noun))),
+                    (c.removed > 0).then(|| format!("−{}", count(c.removed, c.noun))),
+                    (c.renamed > 0).then(|| format!("{} renamed", count(c.renamed, c.noun))),
+                ]
+            })
+            .flatten()
+            .collect::<Vec<_>>();
+        if parts.is_empty() {
+            f.write_str("no changes")
+        } else {
+            f.write_str(&parts.join(", "))
+        }
+    }
+}
+
+/// Compares each tracked table with the staging table replacing it, returning what changed,
+/// and records when the staged rows were first seen: when they were if already cached, `now`
+/// if not, unless their profile was not cached before.
+pub(crate) fn track(conn: &Connection, tracked: &[Tracked], now: i64) -> Result<Summary, Error> {
+    let mut changes = Vec::with_capacity(tracked.len());
+    for t in tracked {
+        let table = t.resource;
+        let staging = crate::staging::table(table);
+        let on = primary_key(conn, table)?
+            .iter()
+            .map(|column| format!("m.\"{column}\" = s.\"{column}\""))
+            .collect::<Vec<_>>()
+            .join(" AND ");
+        let count = |sql: &str| conn.query_row(sql, [], |row| row.get::<_, u64>(0));
+
+        let cached = count(&format!("SELECT EXISTS (SELECT 1 FROM main.{table})"))? > 0;
+        let added = count(&format!(
+            "SELECT COUNT(*) FROM temp.{staging} s
+             WHERE NOT EXISTS (SELECT 1 FROM main.{table} m WHERE {on})"
+        ))?;
+        let removed = count(&format!(
+            "SELECT COUNT(*) FROM main.{table} m
+             WHERE NOT EXISTS (SELECT 1 FROM temp.{staging} s WHERE {on})"
+        ))?;
+        let renamed = count(&format!(
+            "SELECT COUNT(*) FROM temp.{staging} s JOIN main.{table} m ON {on}
+             WHERE m.\"{title}\" IS NOT s.\"{title}\"",
+            title = t.title
+        ))?;
+        // the first refresh, of the table or of a profile, did not see anything appear
+        let first_seen = cached.then_some(now);
+        let profile_cached = t.profile.map_or_else(
+            || "1".to_owned(),
+            |p| format!("EXISTS (SELECT 1 FROM main.{table} m WHERE m.\"{p}\" = s.\"{p}\")"),
+        );
+        conn.execute(
+            &format!(
+                "UPDATE temp.{staging} AS s SET {FIRST_SEEN} = CASE
+                    WHEN EXISTS (SELECT 1 FROM main.{table} m WHERE {on})
+                    THEN (SELECT m.{FIRST_SEEN} FROM main.{table} m WHERE {on})
+                    WHEN {profile_cached} THEN ?1
+                    END"
+            ),
+            [first_seen],
+        )?;
+        changes.push(Change {
+            noun: t.noun,
+            added,
+            removed,
+            renamed,
+        });
+    }
+    Ok(Summary { changes })
+}
+
+/// A cached row that first appeared recently.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NewRow {
+    pub noun: &'static str,
+    pub profile: String,
+    pub title: String,
+    pub url: String,
+    pub first_seen: DateTime<Utc>,
+}
+
+/// Returns the rows of the tracked tables first seen since `since` whose title matches the
+/// query, newest first.
+///
+/// # Errors
+///
+/// Will return `Err` if a table cannot be read.
+///
+/// # Examples
+///
+/// ```
+/// use alfred_workflow::changes::{self, Tracked};
+/// use alfred_workflow::search::SearchQuery;
+/// use anyhow::Error;
+/// use chrono::{TimeDelta, Utc};
+/// use rusqlite::Connection;
+///
+/// const TRACKED: &[Tracked] = &[Tracked {
+///     resource: "pipelines",
+///     noun: "pipeline",
+///     title: "name",
+///     profile: None,
+/// }];
+///
+/// fn main() -> Result<(), Error> {
+///     let conn = Connection::open_in_memory()?;
+///     let now = Utc::now().timestamp();
+///     conn.execute_batch(&format!(
+///         "CREATE TABLE pipelines (name TEXT NOT NULL PRIMARY KEY, url TEXT NOT NULL, first_seen INTEGER);
+///          INSERT INTO pipelines VALUES
+///             ('deploy', 'https://buildkite.com/acme/deploy', {now}),
+///             ('docs', 'https://buildkite.com/acme/docs', {}),
+///             ('test', 'https://buildkite.com/acme/test', NULL);",
+///         now - 30 * 86_400
+///     ))?;
+///
+///     let since = Utc::now() - TimeDelta::days(7);
+///     let rows = changes::first_seen_since(&conn, TRACKED, since, &SearchQuery::default())?;
+///     let titles = rows.iter().map(|row| row.title.as_str()).collect::<Vec<_>>();
+///     assert_eq!(titles, ["deploy"]);
+///     assert!(changes::first_seen_since(&conn, TRACKED, since, &SearchQuery::parse("docs"))?.is_empty());
+///     Ok(())
+/// }
+/// ```
+pub fn first_seen_since(
+    conn: &Connection,
+    tracked: &[Tracked],
+    since: DateTime<Utc>,
+    query: &SearchQuery,
+) -> Result<Vec<NewRow>, Error> {
+    let mut rows = Vec::new();
+    for t in tracked {
+        let filter = query.to_sql(&SearchFields {
+            default: &["title"],
+            qualifiers: &[],
+            profile: t.profile.map(|_| "profile"),
+        });
+        let since = since.timestamp();
+        let mut params = vec![&since as &dyn ToSql];
+        params.extend(filter.params.iter().map(|p| p as &dyn ToSql));
+        let mut stmt = conn.prepare(&format!(
+            "SELECT * FROM (
+                SELECT {profile} AS profile, \"{title}\" AS title, url, {FIRST_SEEN} AS first_seen
+                FROM main.{table}
+                WHERE {FIRST_SEEN} >= ?
+            ) WHERE {clause}",
+            profile = t
+                .profile
+                .map_or_else(|| "''".to_owned(), |p| format!("\"{p}\"")),
+            title = t.title,
+            table = t.resource,
+            clause = filter.clause,
+        ))?;
+        let found = stmt.query_map(&*params, |row| {
+            Ok(NewRow {
+                noun: t.noun,
+                profile: row.get(0)?,
+                title: row.get(1)?,
+                url: row.get(2)?,
+                first_seen: DateTime::from_timestamp(row.get(3)?, 0).unwrap_or_default(),
+            })
+        })?;
+        for row in found {
+            rows.push(row?);
+        }
+    }
+    rows.sort_by_key(|row| std::cmp::Reverse(row.first_seen));
+    Ok(rows)
+}
+
+/// Returns the primary key columns of the table, in order.
+fn primary_key(conn: &Connection, table: &str) -> Result<Vec<String>, Error> {
+    let mut columns = conn
+        .prepare(&format!("PRAGMA main.table_info({table})"))?
+        .query_map([], |row| Ok((row.get::<_, i64>("pk")?, row.get("name")?)))?
+        .collect::<Result<Vec<(i64, String)>, _>>()?;
+    if columns.is_empty() {
+        return Err(Error::TableNotFound(table.to_owned()));
+    }
+    columns.retain(|(pk, _)| *pk > 0);
+    if columns.is_empty() {
+        return Err(Error::NoPrimaryKey(table.to_owned()));
+    }
+    columns.sort();
+    Ok(columns.into_iter().map(|(_, name)| name).collect())
+}
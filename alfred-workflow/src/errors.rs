@@ -0,0 +1,126 @@
+use crate::config::Problem;
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    SQLite(#[from] rusqlite::Error),
+
+    #[error("failed to write alfred items->json: {}", _0)]
+    WriteItems(io::Error),
+
+    #[error("failed to write results: {}", _0)]
+    WriteResults(io::Error),
+
+    #[error(
+        "database schema version {found} is newer than the latest supported version {supported}, please upgrade the workflow"
+    )]
+    UnsupportedSchemaVersion { found: u32, supported: u32 },
+
+    #[error("migration version {found} is out of order, expected version {expected}")]
+    MigrationOutOfOrder { found: u32, expected: u32 },
+
+    #[error("cache is empty, refresh the workflow")]
+    CacheEmpty,
+
+    #[error("invalid workflow configuration: {}", join(.0))]
+    InvalidConfig(Vec<Problem>),
+
+    #[error("impossible to get your home dir")]
+    HomeDirNotFound,
+
+    #[error("failed to create directory {}: {}", path.display(), source)]
+    CreateDir { path: PathBuf, source: io::Error },
+
+    #[error("refusing to open {}, it is not an allowed https URL", _0)]
+    UrlNotAllowed(String),
+
+    #[error("failed to execute {}: {}", command, source)]
+    Open { command: String, source: io::Error },
+
+    #[error("{} exited with {}", command, status)]
+    OpenFailed { command: String, status: ExitStatus },
+
+    #[error("failed to start background refresh: {}", _0)]
+    Spawn(io::Error),
+
+    #[error("refresh already in progress")]
+    RefreshInProgress,
+
+    #[error("failed to lock {}: {}", path.display(), source)]
+    Lock { path: PathBuf, source: io::Error },
+
+    #[error("no such table: {}", _0)]
+    TableNotFound(String),
+
+    #[error("table {} has no primary key to track its changes by", _0)]
+    NoPrimaryKey(String),
+
+    #[error(transparent)]
+    Request(#[from] reqwest::Error),
+
+    #[error("rate limited by the API, try again in {}s", _0.as_secs())]
+    RateLimited(Duration),
+
+    #[error("invalid proxy: {}", _0)]
+    InvalidProxy(reqwest::Error),
+
+    #[error("invalid CA bundle {}: {}", path.display(), reason)]
+    InvalidCaBundle { path: PathBuf, reason: String },
+
+    #[error(
+        "the TLS certificate of {} is not trusted ({}), if a proxy intercepts TLS set CA_BUNDLE to its root certificate",
+        host,
+        reason
+    )]
+    UntrustedCertificate { host: String, reason: String },
+
+    #[error("secret variable {} is not set", _0)]
+    SecretVariable(String),
+
+    #[error("failed to read secret file {}: {}", path.display(), source)]
+    SecretFile { path: PathBuf, source: io::Error },
+
+    #[error(
+        "secret file {} may be read by other users (mode {:o}), restrict it with chmod 600",
+        path.display(),
+        mode
+    )]
+    SecretFilePermissions { path: PathBuf, mode: u32 },
+
+    #[error("secret command `{}` failed: {}", command, reason)]
+    SecretCommand { command: String, reason: String },
+
+    #[error("secret {} is empty", _0)]
+    SecretEmpty(String),
+
+    #[error("invalid snapshot: {}", _0)]
+    InvalidSnapshot(String),
+
+    #[error("snapshot format version {found} is not supported, expected version {supported}")]
+    SnapshotVersion { found: u64, supported: u64 },
+
+    #[error("snapshot of the {found} workflow cannot be imported into the {expected} workflow")]
+    SnapshotWorkflow { expected: String, found: String },
+
+    #[error("failed to read snapshot: {}", _0)]
+    ReadSnapshot(io::Error),
+
+    #[error("failed to write snapshot: {}", _0)]
+    WriteSnapshot(io::Error),
+
+    #[error("failed to write results as JSON: {}", _0)]
+    WriteJson(#[from] serde_json::Error),
+}
+
+fn join(problems: &[Problem]) -> String {
+    problems
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
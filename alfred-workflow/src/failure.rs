@@ -0,0 +1,191 @@
+//! Renders the known classes of failure as Alfred items, so a Script Filter shows what went
+//! wrong and how to fix it instead of silently producing no output.
+//!
+use crate::config::Problem;
+use crate::errors::Error;
+use alfred::Item;
+use rusqlite::Connection;
+use std::{env, fmt};
+
+/// Alfred variable holding the bundle id of the running workflow.
+const BUNDLE_ID_VARIABLE: &str = "alfred_workflow_bundleid";
+
+/// A failure the user can act on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Failure {
+    /// A required workflow variable is not set.
+    MissingVariable(String),
+
+    /// Nothing has been cached yet, the workflow needs refreshing.
+    CacheEmpty,
+
+    /// The API rejected the configured credentials.
+    Unauthorized { service: &'static str, status: u16 },
+
+    /// Anything else, with its message.
+    Other(String),
+}
+
+/// Errors which can tell which known failure they are.
+pub trait ToFailure {
+    /// Returns which known failure this is, falling back to `Failure::Other`.
+    fn to_failure(&self) -> Failure;
+}
+
+impl Failure {
+    /// Converts the failure into an item whose arg fixes it.
+    ///
+    /// # Arguments
+    /// * `refresh_arg` - the arg that refreshes the workflow's cache, eg. `refresh`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use alfred_workflow::failure::Failure;
+    ///
+    /// let item = Failure::CacheEmpty.into_item("refresh");
+    /// assert_eq!(item.title, "Cache empty");
+    /// assert_eq!(item.arg.as_deref(), Some("refresh"));
+    ///
+    /// let item = Failure::MissingVariable("API_KEY".to_owned()).into_item("refresh");
+    /// assert_eq!(item.title, "API_KEY not set");
+    /// assert!(item.arg.unwrap().starts_with("open alfredpreferences://"));
+    /// ```
+    #[must_use]
+    pub fn into_item(self, refresh_arg: &str) -> Item<'static> {
+        let title = self.to_string();
+        let (subtitle, arg) = match self {
+            Self::MissingVariable(_) => (
+                "Press enter to open the workflow configuration",
+                format!("open {}", configuration_url()),
+            ),
+            Self::CacheEmpty => (
+                "Press enter to refresh, you will be notified once complete",
+                refresh_arg.to_owned(),
+            ),
+            Self::Unauthorized { .. } => (
+                "Press enter to update the token in the workflow configuration",
+                format!("open {}", configuration_url()),
+            ),
+            Self::Other(_) => (
+                "Press enter to check the workflow configuration",
+                format!("open {}", configuration_url()),
+            ),
+        };
+        alfred::ItemBuilder::new(title.clone())
+            .subtitle(subtitle)
+            .arg(arg)
+            .text_copy(title.clone())
+            .text_large_type(title)
+            .into_item()
+    }
+
+    /// Classifies an error, trying the workflow's own error type `E` first and then the errors
+    /// of this crate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use alfred_workflow::errors::Error;
+    /// use alfred_workflow::failure::Failure;
+    ///
+    /// let err = anyhow::Error::from(Error::CacheEmpty);
+    /// assert_eq!(Failure::classify::<Error>(&err), Failure::CacheEmpty);
+    ///
+    /// let err = anyhow::anyhow!("connection reset");
+    /// assert_eq!(
+    ///     Failure::classify::<Error>(&err),
+    ///     Failure::Other("connection reset".to_owned())
+    /// );
+    /// ```
+    #[must_use]
+    pub fn classify<E>(err: &anyhow::Error) -> Self
+    where
+        E: ToFailure + fmt::Display + fmt::Debug + Send + Sync + 'static,
+    {
+        err.downcast_ref::<E>()
+            .map(ToFailure::to_failure)
+            .or_else(|| err.downcast_ref::<Error>().map(ToFailure::to_failure))
+            .unwrap_or_else(|| Self::Other(err.to_string()))
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingVariable(name) => write!(f, "{name} not set"),
+            Self::CacheEmpty => f.write_str("Cache empty"),
+            Self::Unauthorized { service, status } => {
+                write!(f, "Token rejected by {service} ({status})")
+            }
+            Self::Other(message) => f.write_str(message),
+        }
+    }
+}
+
+impl ToFailure for Error {
+    fn to_failure(&self) -> Failure {
+        match self {
+            Self::CacheEmpty => Failure::CacheEmpty,
+            Self::InvalidConfig(problems) => problems
+                .iter()
+                .map(|p| match p {
+                    Problem::Missing(key) => Some(key.as_str()),
+                    Problem::Malformed { .. } => None,
+                })
+                .collect::<Option<Vec<_>>>()
+                .map_or_else(
+                    || Failure::Other(self.to_string()),
+                    |keys| Failure::MissingVariable(keys.join(", ")),
+                ),
+            _ => Failure::Other(self.to_string()),
+        }
+    }
+}
+
+/// Returns `Err(Error::CacheEmpty)` if every one of the given tables is empty.
+///
+/// Call it when a search returns no results, to tell an empty cache apart from a query that
+/// matches nothing.
+///
+/// # Errors
+///
+/// Will return `Err` if the tables are empty or cannot be read.
+///
+/// # Examples
+///
+/// ```
+/// use alfred_workflow::failure::{self, Failure, ToFailure};
+/// use rusqlite::Connection;
+///
+/// let conn = Connection::open_in_memory().unwrap();
+/// conn.execute("CREATE TABLE repositories (name TEXT NOT NULL)", []).unwrap();
+///
+/// let err = failure::ensure_cached(&conn, &["repositories"]).unwrap_err();
+/// assert_eq!(err.to_failure(), Failure::CacheEmpty);
+///
+/// conn.execute("INSERT INTO repositories (name) VALUES ('payments')", []).unwrap();
+/// assert!(failure::ensure_cached(&conn, &["repositories"]).is_ok());
+/// ```
+pub fn ensure_cached(conn: &Connection, tables: &[&str]) -> Result<(), Error> {
+    for table in tables {
+        let cached: bool = conn.query_row(
+            &format!("SELECT EXISTS(SELECT 1 FROM {table})"),
+            [],
+            |row| row.get(0),
+        )?;
+        if cached {
+            return Ok(());
+        }
+    }
+    Err(Error::CacheEmpty)
+}
+
+/// Returns the URL that opens the running workflow's configuration in Alfred Preferences.
+#[must_use]
+pub fn configuration_url() -> String {
+    env::var(BUNDLE_ID_VARIABLE).map_or_else(
+        |_| "alfredpreferences://navigateto/workflows".to_owned(),
+        |id| format!("alfredpreferences://navigateto/workflows>workflow>{id}>userconfig"),
+    )
+}
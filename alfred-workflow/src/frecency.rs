@@ -0,0 +1,97 @@
+//! Records which results are opened so frequently and recently used ones can be ranked first.
+//!
+use crate::errors::Error;
+use rusqlite::{Connection, ToSql};
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Alfred variable set on result items holding the query that produced them; Alfred passes it
+/// to the open action as an environment variable.
+pub const QUERY_VARIABLE: &str = "alfred_workflow_query";
+
+/// Schema of the selections table, to be included in a workflow's migrations.
+pub const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS selections (
+        key         TEXT    NOT NULL,
+        query       TEXT    NOT NULL,
+        selected_at INTEGER NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_selections_key ON selections (key);";
+
+/// Selections older than this are forgotten.
+const MAX_AGE_SECS: i64 = 180 * 24 * 60 * 60;
+
+/// Records that the result identified by `key` was opened for the given query.
+///
+/// # Errors
+///
+/// Will return `Err` if the selection cannot be written to the database.
+///
+/// # Examples
+///
+/// ```
+/// use alfred_workflow::{frecency, migrations::{self, Migration}};
+/// use anyhow::Error;
+/// use rusqlite::Connection;
+///
+/// fn main() -> Result<(), Error> {
+///     let mut conn = Connection::open_in_memory()?;
+///     migrations::run(&mut conn, &[Migration::new(1, "create selections table", frecency::SCHEMA)])?;
+///
+///     frecency::record(&conn, "https://github.com/acme/payments", "pay")?;
+///     frecency::record(&conn, "https://github.com/acme/payments", "")?;
+///     frecency::record(&conn, "https://github.com/acme/api", "api")?;
+///
+///     let scores = frecency::scores(&conn, "pay")?;
+///     assert!(scores["https://github.com/acme/payments"] > scores["https://github.com/acme/api"]);
+///     Ok(())
+/// }
+/// ```
+pub fn record(conn: &Connection, key: &str, query: &str) -> Result<(), Error> {
+    let now = unix_now();
+    conn.execute(
+        "INSERT INTO selections (key, query, selected_at) VALUES (?1, ?2, ?3)",
+        [&key as &dyn ToSql, &query.trim(), &now],
+    )?;
+    conn.execute(
+        "DELETE FROM selections WHERE selected_at < ?1",
+        [now - MAX_AGE_SECS],
+    )?;
+    Ok(())
+}
+
+/// Returns the frecency score of every previously opened key.
+///
+/// Each selection contributes less the older it is, and double if it was made using a query
+/// starting with the current one.
+///
+/// # Errors
+///
+/// Will return `Err` if the selections cannot be read from the database.
+pub fn scores(conn: &Connection, query: &str) -> Result<HashMap<String, i64>, Error> {
+    let query = query.trim().to_lowercase();
+    conn.prepare(
+        "SELECT key, SUM(
+            CASE
+                WHEN ?1 - selected_at < 4 * 86400 THEN 100
+                WHEN ?1 - selected_at < 14 * 86400 THEN 70
+                WHEN ?1 - selected_at < 31 * 86400 THEN 50
+                WHEN ?1 - selected_at < 90 * 86400 THEN 30
+                ELSE 10
+            END * CASE WHEN ?2 != '' AND substr(lower(query), 1, length(?2)) = ?2 THEN 2 ELSE 1 END
+        )
+        FROM selections
+        GROUP BY key",
+    )?
+    .query_map([&unix_now() as &dyn ToSql, &query], |row| {
+        Ok((row.get(0)?, row.get(1)?))
+    })?
+    .map(|r| Ok(r?))
+    .collect()
+}
+
+#[inline]
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| i64::try_from(d.as_secs()).unwrap_or(i64::MAX))
+}
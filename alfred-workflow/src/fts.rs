@@ -0,0 +1,405 @@
+//! A full-text search index of the cache, an `SQLite` FTS5 table ranking matches using BM25.
+//!
+//! Every cached resource is indexed in the one `search_index` table, whose content is kept in
+//! `search_entries`: the words of each row along with its resource, key and URL. Words are
+//! split on anything but letters and digits, eg. `-`, `_` and `/`, and on camelCase humps, so
+//! `PaymentsAPI-latency` is found searching for `api`, `latency` or `paymentsapi`.
+//!
+//! When the index matches fewer rows than a page of results, the rows whose title fuzzy
+//! matches, see `SearchQuery::to_sql`, are found too, so `pymts` still finds `payments-api`;
+//! they rank after the rows the index matched. Only titles with a word starting with the first
+//! letter of each term are fuzzy matched, which the index finds quickly.
+//!
+//! The index is rebuilt rather than updated, whenever the cache is replaced by a refresh or a
+//! snapshot is imported. Entries are stored shortest title first, so when a search matches
+//! many rows only the shortest, which BM25 ranks best, need ranking.
+//!
+use crate::errors::Error;
+use crate::search::{SearchFields, SearchQuery, SqlFilter};
+use log::debug;
+use rusqlite::Connection;
+use rusqlite::functions::FunctionFlags;
+use std::fmt::Write;
+
+/// Creates the `search_entries` and `search_index` tables; `fts_words` must be registered for
+/// the rows to be indexed using it.
+///
+/// The resource is indexed too, so searches only rank the matches of the searched resources.
+pub const SCHEMA: &str = "CREATE TABLE search_entries (
+        resource    TEXT NOT NULL,
+        key                  NOT NULL,
+        url         TEXT NOT NULL,
+        title       TEXT NOT NULL,
+        description TEXT NOT NULL,
+        tags        TEXT NOT NULL
+    );
+    CREATE INDEX idx_search_entries_url ON search_entries (url);
+    CREATE VIRTUAL TABLE search_index USING fts5(
+        title,
+        description,
+        tags,
+        resource,
+        content = 'search_entries',
+        tokenize = 'unicode61 remove_diacritics 2',
+        prefix = '1 2 3'
+    );";
+
+/// The SQL function returning the words of a text to index, see `words`.
+pub const WORDS_FUNCTION: &str = "fts_words";
+
+/// How much a match in the title, description, tags and resource counts towards the BM25 rank.
+const WEIGHTS: &str = "10.0, 2.0, 1.0, 0.0";
+
+/// The most title matches, and the most matches in any column, a search ranks besides opened
+/// ones, and the most rows it returns to be ranked by `fuzzy::rank`, so it stays fast however
+/// many rows match.
+pub const CANDIDATES: usize = 200;
+
+/// Fewer matches than a page of results and the rows whose title fuzzy matches are searched
+/// too.
+const FEW: usize = 10;
+
+/// The indexed title of `search_entries`, which the fuzzy fallback matches.
+const INDEXED_TITLE: SearchFields = SearchFields {
+    default: &["e.title"],
+    qualifiers: &[],
+    profile: None,
+};
+
+/// A cached resource and how its rows are indexed.
+#[derive(Debug, Clone, Copy)]
+pub struct Source {
+    pub resource: &'static str,
+    /// Selects the `key` identifying each row, its `url` and the `title`, `description` and
+    /// `tags` text indexed for it.
+    pub select: &'static str,
+}
+
+/// A search compiled into a join of the matching rows of the index and a filter of the rest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SqlSearch {
+    /// Joins the rows of the index matching the query's text, or fuzzy matching it if the index
+    /// matched few, empty if it has none.
+    pub join: String,
+    /// Filters the rows by the negated and qualified terms.
+    pub clause: String,
+    /// The join's parameters followed by the filter's.
+    pub params: Vec<String>,
+}
+
+impl SqlSearch {
+    /// Orders by BM25 rank, best match first, if the query's text was matched, then the rows
+    /// only fuzzy matching, then by `then`.
+    #[must_use]
+    pub fn order_by(&self, then: &str) -> String {
+        if self.join.is_empty() {
+            then.to_owned()
+        } else {
+            format!("fts.rank IS NULL, fts.rank, {then}")
+        }
+    }
+}
+
+/// Registers the `fts_words` function on the connection, which indexing uses.
+///
+/// # Errors
+///
+/// Will return `Err` if the function cannot be registered.
+pub fn register(conn: &Connection) -> Result<(), Error> {
+    conn.create_scalar_function(
+        WORDS_FUNCTION,
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| {
+            let text = ctx.get::<Option<String>>(0)?;
+            Ok(words(text.as_deref().unwrap_or_default()))
+        },
+    )?;
+    Ok(())
+}
+
+/// Splits the text into words, at anything but letters and digits and at camelCase humps.
+///
+/// # Examples
+///
+/// ```
+/// use alfred_workflow::fts::split;
+///
+/// assert_eq!(split("payments-api/v2_beta"), ["payments", "api", "v2", "beta"]);
+/// assert_eq!(split("PaymentsAPILatency"), ["Payments", "API", "Latency"]);
+/// assert_eq!(split("--"), Vec::<&str>::new());
+/// ```
+#[must_use]
+pub fn split(text: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    for run in text.split(|c: char| !c.is_alphanumeric()) {
+        let chars = run.char_indices().collect::<Vec<_>>();
+        let mut start = 0;
+        for i in 1..chars.len() {
+            let (at, c) = chars[i];
+            let previous = chars[i - 1].1;
+            let next = chars.get(i + 1).map(|(_, c)| *c);
+            let hump = (previous.is_lowercase() && c.is_uppercase())
+                || (previous.is_uppercase()
+                    && c.is_uppercase()
+                    && next.is_some_and(char::is_lowercase));
+            if hump {
+                words.push(&run[start..at]);
+                start = at;
+            }
+        }
+        if !run.is_empty() {
+            words.push(&run[start..]);
+        }
+    }
+    words
+}
+
+/// Returns the words of the text to index: its `split` words, then the camelCase words whole so
+/// they can be searched for as written.
+///
+/// # Examples
+///
+/// ```
+/// use alfred_workflow::fts::words;
+///
+/// assert_eq!(words("payments-api"), "payments api");
+/// assert_eq!(words("PaymentsAPI latency"), "Payments API latency PaymentsAPI");
+/// ```
+#[must_use]
+pub fn words(text: &str) -> String {
+    let mut words = split(text);
+    let whole = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|run| split(run).len() > 1)
+        .collect::<Vec<_>>();
+    words.extend(whole);
+    words.join(" ")
+}
+
+/// Replaces the index of the resources with their current rows, in the caller's transaction if
+/// any.
+///
+/// # Errors
+///
+/// Will return `Err` if a resource cannot be read or the index cannot be written.
+///
+/// # Examples
+///
+/// ```
+/// use alfred_workflow::fts::{self, Source};
+/// use anyhow::Error;
+/// use rusqlite::Connection;
+///
+/// const INDEX: &[Source] = &[Source {
+///     resource: "repositories",
+///     select: "SELECT name AS key, url, name AS title, description, '' AS tags FROM repositories",
+/// }];
+///
+/// fn main() -> Result<(), Error> {
+///     let conn = Connection::open_in_memory()?;
+///     fts::register(&conn)?;
+///     conn.execute_batch(fts::SCHEMA)?;
+///     conn.execute_batch(
+///         "CREATE TABLE repositories (name TEXT NOT NULL PRIMARY KEY, url TEXT, description TEXT);
+///          INSERT INTO repositories VALUES ('paymentGateway', 'https://example.com', 'Takes cards');",
+///     )?;
+///
+///     fts::rebuild(&conn, INDEX)?;
+///     let key: String = conn.query_row(
+///         "SELECT key FROM search_entries
+///          WHERE rowid IN (SELECT rowid FROM search_index WHERE search_index MATCH 'gateway')",
+///         [],
+///         |row| row.get(0),
+///     )?;
+///     assert_eq!(key, "paymentGateway");
+///     Ok(())
+/// }
+/// ```
+pub fn rebuild(conn: &Connection, sources: &[Source]) -> Result<(), Error> {
+    if sources.is_empty() {
+        return Ok(());
+    }
+    let selects = sources
+        .iter()
+        .map(|source| {
+            format!(
+                "SELECT '{}' AS resource, key, url, {WORDS_FUNCTION}(title) AS title,
+                    {WORDS_FUNCTION}(description) AS description, {WORDS_FUNCTION}(tags) AS tags
+                 FROM ({})",
+                source.resource, source.select
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(" UNION ALL ");
+    conn.execute("DELETE FROM search_entries", [])?;
+    let rows = conn.execute(
+        &format!(
+            "INSERT INTO search_entries (resource, key, url, title, description, tags)
+             SELECT * FROM ({selects}) ORDER BY length(title), title"
+        ),
+        [],
+    )?;
+    debug!("indexed {rows} rows");
+    // from scratch, which leaves the index in one segment, the fastest to query
+    conn.execute(
+        "INSERT INTO search_index (search_index) VALUES ('rebuild')",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Compiles the query into a join of the index, matching its plain and phrase terms as
+/// prefixes of words and phrases ignoring case, and an SQL filter of the rest, see
+/// `SearchQuery::to_sql`. If the index matches fewer than a page of rows, rows whose indexed
+/// title the plain and phrase terms match as `SearchQuery::to_sql` matches them are joined
+/// too, eg. `pymts` for `payments`.
+///
+/// Only the shortest title matches and matches in any column are ranked and joined, along with
+/// every match opened before so it can still be ranked by `frecency`, whose selections table
+/// the cache must have. The join's parameters are `?1` and `?2`, so it must come before any
+/// other. Searches should be limited to `CANDIDATES` rows.
+///
+/// # Arguments
+/// * `query` - the search query.
+/// * `resources` - the resources searched.
+/// * `on` - joins the index's `fts.resource` and `fts.key` to the searched rows, eg.
+///   `fts.key = m.id`.
+/// * `fields` - the columns the negated and qualified terms match against.
+///
+/// # Examples
+///
+/// ```
+/// use alfred_workflow::fts;
+/// use alfred_workflow::search::{SearchFields, SearchQuery};
+///
+/// const FIELDS: SearchFields = SearchFields {
+///     default: &["name"],
+///     qualifiers: &[("owner", "owner")],
+///     profile: None,
+/// };
+///
+/// let query = SearchQuery::parse(r#"paymentsApi "web v2" -legacy owner:acme"#);
+/// let search = fts::compile(&query, &["repositories"], "fts.key = name", &FIELDS);
+/// assert_eq!(search.params[0], r#""payments"* "api"* "web v2""#);
+/// // the fuzzy fallback
+/// assert_eq!(&search.params[1..4], [r#""p"* "w"*"#, "%p%a%y%m%e%n%t%s%A%p%i%", "%web v2%"]);
+/// assert_eq!(&search.params[4..], ["%legacy%", "%acme%"]);
+/// assert_eq!(search.order_by("name"), "fts.rank IS NULL, fts.rank, name");
+///
+/// let search = fts::compile(&SearchQuery::parse("owner:acme"), &["repositories"], "fts.key = name", &FIELDS);
+/// assert!(search.join.is_empty());
+/// ```
+#[must_use]
+pub fn compile(
+    query: &SearchQuery,
+    resources: &[&str],
+    on: &str,
+    fields: &SearchFields,
+) -> SqlSearch {
+    let (text, rest) = query.partition_text(fields);
+    let mut expression = String::new();
+    for term in &text {
+        let words = split(&term.value)
+            .into_iter()
+            .map(str::to_lowercase)
+            .collect::<Vec<_>>();
+        if words.is_empty() {
+            continue;
+        }
+        if !expression.is_empty() {
+            expression.push(' ');
+        }
+        if term.phrase {
+            let _ = write!(expression, "\"{}\"", words.join(" "));
+        } else {
+            let prefixes = words
+                .iter()
+                .map(|word| format!("\"{word}\"*"))
+                .collect::<Vec<_>>();
+            expression.push_str(&prefixes.join(" "));
+        }
+    }
+
+    let SqlFilter { clause, params } = rest.to_sql(fields);
+    if expression.is_empty() {
+        return SqlSearch {
+            join: String::new(),
+            clause,
+            params,
+        };
+    }
+    // the words starting with the first letter of each term bound the rows fuzzy matched
+    let initials = text
+        .iter()
+        .filter_map(|term| split(&term.value).first()?.chars().next())
+        .map(|initial| format!("\"{}\"*", initial.to_lowercase()))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let fuzzy = SearchQuery::from_terms(text).to_sql(&INDEXED_TITLE);
+    let phrases = resources
+        .iter()
+        .map(|resource| format!("\"{resource}\""))
+        .collect::<Vec<_>>()
+        .join(" OR ");
+    let resources = resources
+        .iter()
+        .map(|resource| format!("'{resource}'"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    SqlSearch {
+        join: format!(
+            "JOIN (
+                WITH matched AS MATERIALIZED (
+                    SELECT e.resource, e.key, matches.rank
+                    FROM (
+                        SELECT rowid, bm25(search_index, {WEIGHTS}) AS rank FROM search_index
+                        WHERE search_index MATCH '{{title description tags}} : (' || ?1 || ')'
+                        -- scanning the matches is faster than looking up each candidate
+                        AND +rowid IN (
+                            SELECT rowid FROM (
+                                SELECT rowid FROM search_index
+                                WHERE search_index MATCH 'resource : ({phrases}) AND title : (' || ?1 || ')'
+                                ORDER BY rowid LIMIT {CANDIDATES}
+                            )
+                            UNION ALL
+                            SELECT rowid FROM (
+                                SELECT rowid FROM search_index
+                                WHERE search_index MATCH 'resource : ({phrases}) AND {{title description tags}} : (' || ?1 || ')'
+                                ORDER BY rowid LIMIT {CANDIDATES}
+                            )
+                            UNION ALL
+                            SELECT rowid FROM search_entries
+                            WHERE url IN (SELECT key FROM main.selections) AND resource IN ({resources})
+                        )
+                    ) matches
+                    CROSS JOIN search_entries e ON e.rowid = matches.rowid
+                    ORDER BY e.url IN (SELECT key FROM main.selections) DESC, matches.rank
+                    LIMIT {CANDIDATES}
+                )
+                SELECT resource, key, rank FROM matched
+                UNION ALL
+                SELECT * FROM (
+                    SELECT e.resource, e.key, NULL AS rank
+                    FROM (
+                        SELECT rowid FROM search_index
+                        WHERE search_index MATCH 'resource : ({phrases}) AND title : (' || ?2 || ')'
+                    ) initials
+                    CROSS JOIN search_entries e ON e.rowid = initials.rowid
+                    WHERE {}
+                    AND NOT EXISTS (SELECT 1 FROM matched WHERE matched.resource = e.resource AND matched.key = e.key)
+                    -- a limit of 0 skips searching altogether, unlike a filter
+                    LIMIT CASE WHEN (SELECT COUNT(*) FROM matched) < {FEW} THEN {CANDIDATES} ELSE 0 END
+                )
+            ) fts
+            ON {on}",
+            fuzzy.clause
+        ),
+        clause,
+        params: [expression, initials]
+            .into_iter()
+            .chain(fuzzy.params)
+            .chain(params)
+            .collect(),
+    }
+}
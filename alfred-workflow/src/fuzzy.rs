@@ -0,0 +1,181 @@
+//! Fuzzy match scoring, in the spirit of fzf and Alfred's own matcher.
+//!
+//! A pattern matches a candidate when all of its characters appear in the candidate in order.
+//! Matches at word boundaries, camel-case humps and consecutive runs score higher, as do
+//! candidates the pattern is an exact prefix, whole word or acronym of.
+//!
+use crate::search::SearchQuery;
+
+const SCORE_MATCH: i64 = 16;
+const PENALTY_GAP: i64 = 1;
+const BONUS_BOUNDARY: i64 = 8;
+const BONUS_CAMEL: i64 = 7;
+const BONUS_CONSECUTIVE: i64 = 5;
+const BONUS_FIRST_CHAR_MULTIPLIER: i64 = 2;
+const BONUS_PREFIX: i64 = 48;
+const BONUS_ACRONYM: i64 = 40;
+const BONUS_EXACT: i64 = 96;
+const BONUS_WORD: i64 = 32;
+
+/// Scores how well `pattern` matches `candidate`, case-insensitively.
+///
+/// Returns `None` if the pattern is not a subsequence of the candidate.
+///
+/// # Examples
+///
+/// ```
+/// use alfred_workflow::fuzzy::score;
+///
+/// // exact and prefix matches beat scattered ones
+/// assert!(score("payments", "payments").unwrap() > score("payments", "payments-api").unwrap());
+/// assert!(score("pay", "payments-api").unwrap() > score("pay", "deploy-gateway").unwrap());
+///
+/// // word boundaries, camel-case humps and acronyms
+/// assert!(score("api", "payments-api").unwrap() > score("api", "rapid").unwrap());
+/// assert!(score("ps", "PaymentService").unwrap() > score("ps", "upstream").unwrap());
+/// assert!(score("pa", "payments-api").is_some());
+///
+/// // whole words beat the start of camel-case words
+/// assert!(score("payments", "payments-api").unwrap() > score("payments", "paymentService").unwrap());
+///
+/// assert_eq!(score("xyz", "payments-api"), None);
+/// ```
+#[must_use]
+pub fn score(pattern: &str, candidate: &str) -> Option<i64> {
+    let pattern = pattern
+        .chars()
+        .flat_map(char::to_lowercase)
+        .collect::<Vec<_>>();
+    if pattern.is_empty() {
+        return Some(0);
+    }
+    let original = candidate.chars().collect::<Vec<_>>();
+    let lower = original
+        .iter()
+        .map(|c| c.to_lowercase().next().unwrap_or(*c))
+        .collect::<Vec<_>>();
+    if pattern.len() > lower.len() {
+        return None;
+    }
+    let bonuses = (0..original.len())
+        .map(|i| position_bonus(&original, i))
+        .collect::<Vec<_>>();
+
+    // `matched[j]` is the best score with the current pattern char matched exactly at `j`,
+    // `best[j]` the best score with the current pattern char matched anywhere up to `j`.
+    let mut prev_matched = vec![None; lower.len()];
+    let mut prev_best = vec![None; lower.len()];
+    for (i, p) in pattern.iter().enumerate() {
+        let mut matched = vec![None; lower.len()];
+        let mut best: Vec<Option<i64>> = vec![None; lower.len()];
+        for j in 0..lower.len() {
+            if lower[j] == *p {
+                let bonus = if i == 0 {
+                    bonuses[j] * BONUS_FIRST_CHAR_MULTIPLIER
+                } else {
+                    bonuses[j]
+                };
+                let previous = if i == 0 {
+                    Some(0)
+                } else if j == 0 {
+                    None
+                } else {
+                    let consecutive =
+                        prev_matched[j - 1].map(|s: i64| s + BONUS_CONSECUTIVE.max(bonuses[j]));
+                    consecutive.max(prev_best[j - 1])
+                };
+                matched[j] = previous.map(|s| s + SCORE_MATCH + bonus);
+            }
+            let carried = if j == 0 {
+                None
+            } else {
+                best[j - 1].map(|s: i64| if i == 0 { s } else { s - PENALTY_GAP })
+            };
+            best[j] = matched[j].max(carried);
+        }
+        prev_matched = matched;
+        prev_best = best;
+    }
+
+    let mut total = prev_matched.into_iter().flatten().max()?;
+    if lower == pattern {
+        total += BONUS_EXACT;
+    } else if lower.starts_with(&pattern) {
+        total += BONUS_PREFIX;
+    }
+    let initials = (0..original.len())
+        .filter(|&i| bonuses[i] >= BONUS_CAMEL && original[i].is_alphanumeric())
+        .map(|i| lower[i])
+        .collect::<Vec<_>>();
+    if pattern.len() > 1 && initials.starts_with(&pattern) {
+        total += BONUS_ACRONYM;
+    }
+    let pattern = pattern.into_iter().collect::<String>();
+    if crate::fts::split(candidate)
+        .iter()
+        .any(|word| word.to_lowercase() == pattern)
+    {
+        total += BONUS_WORD;
+    }
+    Some(total)
+}
+
+/// Ranks `items` by how well their text matches the query plus their `boost`, best first.
+///
+/// The sort is stable so items that score the same keep their incoming order; fetch them
+/// sorted by recency to rank by score first and recency second. Items whose text does not
+/// fuzzy match, eg. those that only matched a qualifier, are kept and ranked by boost alone.
+/// Every item is scored, so fetch at most `fts::CANDIDATES` of them.
+///
+/// # Examples
+///
+/// ```
+/// use alfred_workflow::fuzzy::rank;
+/// use alfred_workflow::search::SearchQuery;
+///
+/// // ordered by most recently pushed
+/// let repos = vec!["payment-gateway-api", "old-payments", "payments"];
+/// let query = SearchQuery::parse("payments");
+///
+/// let ranked = rank(&query, repos.clone(), 2, |r| r, |_| 0);
+/// assert_eq!(ranked, ["payments", "old-payments"]);
+///
+/// // eg. frecency of frequently opened results
+/// let ranked = rank(&query, repos, 2, |r| r, |r| if *r == "payment-gateway-api" { 500 } else { 0 });
+/// assert_eq!(ranked, ["payment-gateway-api", "payments"]);
+/// ```
+pub fn rank<T, F, B>(query: &SearchQuery, items: Vec<T>, limit: usize, text: F, boost: B) -> Vec<T>
+where
+    F: Fn(&T) -> &str,
+    B: Fn(&T) -> i64,
+{
+    let mut scored = items
+        .into_iter()
+        .map(|item| {
+            let score = query.score(text(&item)).unwrap_or(0) + boost(&item);
+            (score, item)
+        })
+        .collect::<Vec<_>>();
+    scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    scored
+        .into_iter()
+        .take(limit)
+        .map(|(_, item)| item)
+        .collect()
+}
+
+fn position_bonus(chars: &[char], i: usize) -> i64 {
+    let current = chars[i];
+    let Some(&previous) = i.checked_sub(1).and_then(|p| chars.get(p)) else {
+        return BONUS_BOUNDARY;
+    };
+    if !previous.is_alphanumeric() && current.is_alphanumeric() {
+        BONUS_BOUNDARY
+    } else if (previous.is_lowercase() && current.is_uppercase())
+        || (!previous.is_numeric() && current.is_numeric())
+    {
+        BONUS_CAMEL
+    } else {
+        0
+    }
+}
@@ -0,0 +1,413 @@
+//! A blocking HTTP client shared by the workflows' API clients.
+//!
+//! It reuses connections across requests, retries connection errors, timeouts and `5xx`
+//! responses with exponential backoff, and honours the rate-limit headers of the GitHub,
+//! Buildkite and Datadog APIs, waiting for the limit to reset when that is soon enough instead
+//! of failing a long refresh part way through.
+//!
+//! Proxies are read from the `HTTPS_PROXY` and `NO_PROXY` environment variables unless set on
+//! the builder, and the roots of a TLS-intercepting proxy can be trusted using `ca_bundle`.
+//!
+use crate::errors::Error;
+use log::{debug, info, warn};
+use reqwest::StatusCode;
+use reqwest::blocking::{RequestBuilder, Response};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{Certificate, NoProxy, Proxy};
+use std::cell::Cell;
+use std::error::Error as _;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::{fs, thread};
+
+pub(crate) const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_RETRIES: u32 = 3;
+const DEFAULT_BACKOFF: Duration = Duration::from_millis(500);
+const DEFAULT_MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+const DEFAULT_USER_AGENT: &str = "alfred-workflows-rs";
+
+/// Headers holding the requests remaining in the current window; GitHub and Datadog use the
+/// `X-` prefixed ones, Buildkite the others.
+const REMAINING_HEADERS: &[&str] = &["x-ratelimit-remaining", "ratelimit-remaining"];
+
+/// Headers holding when the current window resets.
+const RESET_HEADERS: &[&str] = &["x-ratelimit-reset", "ratelimit-reset"];
+
+/// Reset values larger than this are Unix timestamps, as GitHub sends, rather than seconds.
+const EPOCH_THRESHOLD: u64 = 1_000_000_000;
+
+/// Builds a `Client`.
+#[derive(Debug, Clone)]
+pub struct ClientBuilder {
+    timeout: Duration,
+    connect_timeout: Duration,
+    retries: u32,
+    backoff: Duration,
+    max_rate_limit_wait: Duration,
+    user_agent: String,
+    proxy: Option<String>,
+    no_proxy: Option<String>,
+    ca_bundle: Option<PathBuf>,
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_TIMEOUT,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            retries: DEFAULT_RETRIES,
+            backoff: DEFAULT_BACKOFF,
+            max_rate_limit_wait: DEFAULT_MAX_RATE_LIMIT_WAIT,
+            user_agent: DEFAULT_USER_AGENT.to_owned(),
+            proxy: None,
+            no_proxy: None,
+            ca_bundle: None,
+        }
+    }
+}
+
+impl ClientBuilder {
+    /// Sets the timeout of each request, from connecting until the body is read.
+    #[inline]
+    #[must_use]
+    pub const fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the timeout for connecting.
+    #[inline]
+    #[must_use]
+    pub const fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Sets how many times a failed request is retried.
+    #[inline]
+    #[must_use]
+    pub const fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Sets the delay before the first retry, doubled for each retry after it.
+    #[inline]
+    #[must_use]
+    pub const fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Sets the longest the client waits for a rate limit to reset before giving up.
+    #[inline]
+    #[must_use]
+    pub const fn max_rate_limit_wait(mut self, wait: Duration) -> Self {
+        self.max_rate_limit_wait = wait;
+        self
+    }
+
+    /// Sets the `User-Agent` sent with every request, which the GitHub API requires.
+    #[inline]
+    #[must_use]
+    pub fn user_agent<S: Into<String>>(mut self, user_agent: S) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Sets the proxy `https://` requests are sent through, eg. `http://proxy.example.com:8080`,
+    /// instead of the one in the `HTTPS_PROXY` environment variable, if any.
+    #[inline]
+    #[must_use]
+    pub fn proxy(mut self, url: Option<&str>) -> Self {
+        self.proxy = url.map(ToOwned::to_owned);
+        self
+    }
+
+    /// Sets the comma separated hosts, domains and IP ranges that bypass the proxy set using
+    /// `proxy`, eg. `localhost,.example.com`.
+    #[inline]
+    #[must_use]
+    pub fn no_proxy(mut self, hosts: Option<&str>) -> Self {
+        self.no_proxy = hosts.map(ToOwned::to_owned);
+        self
+    }
+
+    /// Sets a file of PEM encoded certificates to trust in addition to the built-in roots, eg.
+    /// the root of a TLS-intercepting proxy.
+    #[inline]
+    #[must_use]
+    pub fn ca_bundle(mut self, path: Option<&Path>) -> Self {
+        self.ca_bundle = path.map(Path::to_path_buf);
+        self
+    }
+
+    /// Builds the client.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the proxy URL is invalid, the CA bundle cannot be read, or the
+    /// underlying client cannot be created, eg. the TLS backend fails to initialize.
+    pub fn build(self) -> Result<Client, Error> {
+        let mut builder = reqwest::blocking::Client::builder()
+            .timeout(self.timeout)
+            .connect_timeout(self.connect_timeout)
+            .user_agent(self.user_agent);
+        if let Some(url) = &self.proxy {
+            let proxy = Proxy::https(url).map_err(Error::InvalidProxy)?;
+            let no_proxy = self.no_proxy.as_deref().and_then(NoProxy::from_string);
+            builder = builder.proxy(proxy.no_proxy(no_proxy));
+        }
+        if let Some(path) = self.ca_bundle {
+            for certificate in read_ca_bundle(&path)? {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        let inner = builder.build()?;
+        Ok(Client {
+            inner,
+            retries: self.retries,
+            backoff: self.backoff,
+            max_rate_limit_wait: self.max_rate_limit_wait,
+            resume_at: Cell::new(None),
+        })
+    }
+}
+
+/// A blocking HTTP client with retries and rate-limit handling.
+///
+/// # Examples
+///
+/// ```no_run
+/// use alfred_workflow::http::Client;
+/// use std::time::Duration;
+///
+/// let client = Client::builder()
+///     .timeout(Duration::from_secs(10))
+///     .build()
+///     .unwrap();
+///
+/// let response = client
+///     .send(client.get("https://api.buildkite.com/v2/organizations"))
+///     .unwrap()
+///     .error_for_status()
+///     .unwrap();
+/// ```
+#[derive(Debug)]
+pub struct Client {
+    inner: reqwest::blocking::Client,
+    retries: u32,
+    backoff: Duration,
+    max_rate_limit_wait: Duration,
+    resume_at: Cell<Option<Instant>>,
+}
+
+impl Client {
+    /// Returns a builder with the default timeouts, retries and backoff.
+    #[inline]
+    #[must_use]
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::default()
+    }
+
+    /// Starts a `GET` request, to be sent using `send`.
+    #[inline]
+    pub fn get(&self, url: &str) -> RequestBuilder {
+        self.inner.get(url)
+    }
+
+    /// Starts a `POST` request, to be sent using `send`.
+    #[inline]
+    pub fn post(&self, url: &str) -> RequestBuilder {
+        self.inner.post(url)
+    }
+
+    /// Sends the request, retrying connection errors, timeouts and `5xx` responses and waiting
+    /// out rate limits. Other responses, including errors such as `401`, are returned as is.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the request still fails after retrying, or the API is rate limited
+    /// for longer than the client is willing to wait.
+    pub fn send(&self, request: RequestBuilder) -> Result<Response, Error> {
+        let request = request.build()?;
+        // without the query string, which may hold credentials
+        let target = format!(
+            "{} {}{}",
+            request.method(),
+            request.url().host_str().unwrap_or_default(),
+            request.url().path()
+        );
+        let mut attempt = 0;
+        loop {
+            self.wait_for_reset()?;
+            // a streaming body cannot be cloned, so cannot be retried either
+            let Some(next) = request.try_clone() else {
+                return self
+                    .inner
+                    .execute(request)
+                    .map_err(|e| without_query(e).into());
+            };
+            let retry = attempt < self.retries;
+            let start = Instant::now();
+            match self.inner.execute(next) {
+                Ok(response) => {
+                    let limit = RateLimit::from_headers(response.headers());
+                    let status = response.status();
+                    debug!("{target} {status} in {:?}", start.elapsed());
+                    let limited = status == StatusCode::TOO_MANY_REQUESTS
+                        || (status == StatusCode::FORBIDDEN && limit.remaining == Some(0));
+                    if limited {
+                        let wait = limit.reset.unwrap_or_else(|| self.backoff(attempt));
+                        if !retry || wait > self.max_rate_limit_wait {
+                            return Err(Error::RateLimited(wait));
+                        }
+                        warn!("{target} rate limited, retrying in {wait:?}");
+                        thread::sleep(wait);
+                    } else if status.is_server_error() && retry {
+                        let wait = self.backoff(attempt);
+                        warn!("{target} failed with {status}, retrying in {wait:?}");
+                        thread::sleep(wait);
+                    } else {
+                        if limit.remaining == Some(0) {
+                            self.resume_at
+                                .set(limit.reset.map(|reset| Instant::now() + reset));
+                        }
+                        return Ok(response);
+                    }
+                }
+                Err(e) => {
+                    let e = without_query(e);
+                    if let Some(reason) = certificate_error(&e) {
+                        return Err(Error::UntrustedCertificate {
+                            host: request.url().host_str().unwrap_or_default().to_owned(),
+                            reason,
+                        });
+                    }
+                    if !(retry && (e.is_connect() || e.is_timeout())) {
+                        return Err(e.into());
+                    }
+                    let wait = self.backoff(attempt);
+                    warn!("{target} failed, retrying in {wait:?}: {e}");
+                    thread::sleep(wait);
+                }
+            }
+            attempt += 1;
+        }
+    }
+
+    /// Waits until the rate limit exhausted by a previous request resets.
+    fn wait_for_reset(&self) -> Result<(), Error> {
+        if let Some(resume_at) = self.resume_at.take() {
+            let wait = resume_at.saturating_duration_since(Instant::now());
+            if wait > self.max_rate_limit_wait {
+                self.resume_at.set(Some(resume_at));
+                return Err(Error::RateLimited(wait));
+            }
+            info!("rate limit exhausted, waiting {wait:?} for it to reset");
+            thread::sleep(wait);
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.backoff.saturating_mul(2_u32.saturating_pow(attempt))
+    }
+}
+
+/// The rate limit reported by an API response.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Requests remaining in the current window.
+    pub remaining: Option<u64>,
+
+    /// How long until the window resets, or a rejected request may be retried.
+    pub reset: Option<Duration>,
+}
+
+impl RateLimit {
+    /// Reads the `X-RateLimit-*`, `RateLimit-*` and `Retry-After` headers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use alfred_workflow::http::RateLimit;
+    /// use reqwest::header::HeaderMap;
+    /// use std::time::Duration;
+    ///
+    /// let mut headers = HeaderMap::new();
+    /// headers.insert("RateLimit-Remaining", "0".parse().unwrap());
+    /// headers.insert("RateLimit-Reset", "42".parse().unwrap());
+    ///
+    /// let limit = RateLimit::from_headers(&headers);
+    /// assert_eq!(limit.remaining, Some(0));
+    /// assert_eq!(limit.reset, Some(Duration::from_secs(42)));
+    /// ```
+    #[must_use]
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let remaining = REMAINING_HEADERS.iter().find_map(|h| number(headers, h));
+        let reset = number(headers, RETRY_AFTER.as_str())
+            .or_else(|| RESET_HEADERS.iter().find_map(|h| number(headers, h)))
+            .map(|reset| {
+                if reset > EPOCH_THRESHOLD {
+                    let now = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(0, |d| d.as_secs());
+                    Duration::from_secs(reset.saturating_sub(now))
+                } else {
+                    Duration::from_secs(reset)
+                }
+            });
+        Self { remaining, reset }
+    }
+}
+
+/// Reads the certificates of a PEM encoded CA bundle.
+fn read_ca_bundle(path: &Path) -> Result<Vec<Certificate>, Error> {
+    let invalid = |reason: String| Error::InvalidCaBundle {
+        path: path.to_path_buf(),
+        reason,
+    };
+    let pem = fs::read(path).map_err(|e| invalid(e.to_string()))?;
+    let certificates = Certificate::from_pem_bundle(&pem).map_err(|e| invalid(e.to_string()))?;
+    if certificates.is_empty() {
+        return Err(invalid("it holds no PEM certificates".to_owned()));
+    }
+    Ok(certificates)
+}
+
+/// Returns why the server's certificate was not trusted, if that is why the request failed.
+///
+/// The TLS backends only report this as the text of an underlying error, eg. `invalid peer
+/// certificate: UnknownIssuer` or `certificate verify failed`.
+fn certificate_error(e: &reqwest::Error) -> Option<String> {
+    if !e.is_connect() {
+        return None;
+    }
+    let mut source = e.source();
+    while let Some(err) = source {
+        let message = err.to_string();
+        if message.contains("certificate") {
+            return Some(message);
+        }
+        source = err.source();
+    }
+    None
+}
+
+/// Removes the query string, which may hold credentials, from the URL shown by the error, so
+/// it can be logged or shown to the user.
+#[must_use]
+pub fn without_query(mut e: reqwest::Error) -> reqwest::Error {
+    if let Some(url) = e.url_mut() {
+        url.set_query(None);
+    }
+    e
+}
+
+#[inline]
+fn number(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
@@ -1,10 +1,41 @@
 //! This contains common abstractions for reuse in multiple workflows
 //!
-use alfred::{json, Item};
-use anyhow::{anyhow, Error};
-use rusqlite::Connection;
+pub mod changes;
+pub mod config;
+pub mod errors;
+pub mod failure;
+pub mod frecency;
+pub mod fts;
+pub mod fuzzy;
+pub mod http;
+pub mod logging;
+pub mod migrations;
+#[cfg(feature = "mock-server")]
+pub mod mock_server;
+pub mod opener;
+pub mod output;
+pub mod profile;
+pub mod refresh;
+pub mod response;
+pub mod runner;
+pub mod search;
+pub mod secret;
+pub mod snapshot;
+pub mod staging;
+pub mod workflow;
+
+use crate::migrations::Migration;
+pub use crate::opener::open_url;
+use alfred::{Item, json};
+use anyhow::{Error, anyhow};
+use rusqlite::{Connection, OpenFlags};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use std::{fs, io::Write};
 
+/// How long a read-only connection waits for a writer, eg. a refresh, to finish.
+const READ_ONLY_BUSY_TIMEOUT: Duration = Duration::from_secs(2);
+
 /// Opens or creates if not exists an `SQLite` database.
 ///
 /// # Arguments
@@ -45,11 +76,7 @@ where
     F: Fn(&Connection) -> Result<(), Error>,
 {
     let conn: Connection;
-    let path = dirs::home_dir()
-        .ok_or_else(|| anyhow!("Impossible to get your home dir!"))?
-        .join(".alfred")
-        .join("workflows")
-        .join(name);
+    let path = workflow_dir(name)?;
 
     let db = path.join("db.sqlite3");
     if db.exists() {
@@ -62,6 +89,78 @@ where
     Ok(conn)
 }
 
+/// Opens or creates if not exists an `SQLite` database at the given path, creating its
+/// directory if needed, and brings its schema up to date. Registers `fts_words` for indexing.
+///
+/// Unlike `open_database_or_else` the migrations are checked on every open, so schema changes
+/// are applied to existing databases as well.
+///
+/// # Errors
+///
+/// Will return `Err` if the directory cannot be created, connection to database fails or the
+/// migrations cannot be applied.
+pub fn open_database_at<P: AsRef<Path>>(
+    path: P,
+    migrations: &[Migration],
+) -> Result<Connection, errors::Error> {
+    let path = path.as_ref();
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).map_err(|source| errors::Error::CreateDir {
+            path: dir.to_owned(),
+            source,
+        })?;
+    }
+    let mut conn = Connection::open(path)?;
+    // before migrating, which may index the cache
+    fts::register(&conn)?;
+    migrations::run(&mut conn, migrations)?;
+    Ok(conn)
+}
+
+/// Opens an existing `SQLite` database read-only, eg. another workflow's cache, waiting
+/// briefly if a refresh is swapping in its tables.
+///
+/// # Errors
+///
+/// Will return `Err` if the database does not exist or cannot be opened.
+///
+/// # Examples
+///
+/// ```
+/// use alfred_workflow::migrations::Migration;
+///
+/// let path = std::env::temp_dir().join("read-only-doctest.sqlite3");
+/// let migrations = &[Migration::new(1, "create config table", "CREATE TABLE IF NOT EXISTS config (key TEXT);")];
+/// alfred_workflow::open_database_at(&path, migrations).unwrap();
+///
+/// let conn = alfred_workflow::open_database_read_only(&path).unwrap();
+/// assert!(conn.execute("INSERT INTO config (key) VALUES ('a')", []).is_err());
+/// assert!(alfred_workflow::open_database_read_only(path.with_extension("missing")).is_err());
+/// ```
+pub fn open_database_read_only<P: AsRef<Path>>(path: P) -> Result<Connection, errors::Error> {
+    let conn = Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    conn.busy_timeout(READ_ONLY_BUSY_TIMEOUT)?;
+    Ok(conn)
+}
+
+/// Returns the directory the named workflow keeps its data in when Alfred does not provide
+/// one, `~/.alfred/workflows/<name>`.
+///
+/// # Errors
+///
+/// Will return `Err` if the home directory cannot be determined.
+#[inline]
+pub fn workflow_dir(name: &str) -> Result<PathBuf, errors::Error> {
+    Ok(dirs::home_dir()
+        .ok_or(errors::Error::HomeDirNotFound)?
+        .join(".alfred")
+        .join("workflows")
+        .join(name))
+}
+
 /// Writes Alfred items to the provided writer.
 ///
 /// # Arguments
@@ -0,0 +1,137 @@
+//! Leveled, timestamped logging to a size-rotated file in the workflow's data directory,
+//! mirrored to stderr so it also shows in Alfred's workflow debugger.
+//!
+//! Log using the `log` macros once `init` has been called, eg. `log::info!("...")`.
+//!
+use crate::config::Loader;
+use chrono::{SecondsFormat, Utc};
+use log::{LevelFilter, Log, Metadata, Record};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+/// Variable setting the most verbose level logged, eg. `debug`; `info` by default.
+pub const LEVEL_VARIABLE: &str = "LOG_LEVEL";
+
+/// Name of the log file within the workflow's data directory.
+pub const FILE_NAME: &str = "workflow.log";
+
+/// The log file is rotated once it would grow past this size.
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+
+/// How many rotated files, `workflow.log.1` being the newest, are kept.
+const ROTATED_FILES: usize = 3;
+
+/// Starts logging for the named workflow, at the level set by `LOG_LEVEL`.
+///
+/// Logging never stops the workflow from running: if the log file cannot be opened, logs are
+/// only written to stderr.
+pub fn init(name: &str) {
+    let (level, problems, file) = match Loader::new(name) {
+        Ok(mut loader) => {
+            let level = loader.parse_or(LEVEL_VARIABLE, LevelFilter::Info);
+            let file = RotatingFile::open(loader.data_dir().join(FILE_NAME))
+                .inspect_err(|e| eprintln!("not logging to a file: {e}"))
+                .ok();
+            (level, loader.problems().to_vec(), file)
+        }
+        Err(e) => {
+            eprintln!("not logging to a file: {e}");
+            (LevelFilter::Info, Vec::new(), None)
+        }
+    };
+    let logger = Logger {
+        level,
+        file: Mutex::new(file),
+    };
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(level);
+    }
+    for problem in problems {
+        log::warn!("{problem}");
+    }
+}
+
+struct Logger {
+    level: LevelFilter,
+    file: Mutex<Option<RotatingFile>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!(
+            "{} {:<5} {}: {}\n",
+            Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        // stdout is reserved for the Script Filter's JSON
+        let _ = io::stderr().write_all(line.as_bytes());
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(f) = file.as_mut()
+            && let Err(e) = f.write(line.as_bytes())
+        {
+            eprintln!("stopped logging to a file: {e}");
+            *file = None;
+        }
+    }
+
+    fn flush(&self) {
+        let _ = io::stderr().flush();
+    }
+}
+
+/// A log file that is rotated once it grows past `MAX_FILE_BYTES`.
+struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    len: u64,
+}
+
+impl RotatingFile {
+    fn open(path: PathBuf) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let len = file.metadata()?.len();
+        Ok(Self { path, file, len })
+    }
+
+    fn write(&mut self, line: &[u8]) -> io::Result<()> {
+        let size = line.len() as u64;
+        if self.len > 0 && self.len + size > MAX_FILE_BYTES {
+            self.rotate()?;
+        }
+        self.file.write_all(line)?;
+        self.len += size;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        for n in (1..ROTATED_FILES).rev() {
+            let from = rotated(&self.path, n);
+            if from.exists() {
+                fs::rename(&from, rotated(&self.path, n + 1))?;
+            }
+        }
+        fs::rename(&self.path, rotated(&self.path, 1))?;
+        *self = Self::open(self.path.clone())?;
+        Ok(())
+    }
+}
+
+fn rotated(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{n}"));
+    PathBuf::from(name)
+}
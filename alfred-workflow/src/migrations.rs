@@ -0,0 +1,115 @@
+//! Ordered, numbered schema migrations tracked using `SQLite`'s `user_version` pragma.
+//!
+use crate::errors::Error;
+use rusqlite::Connection;
+
+/// A single schema migration step.
+///
+/// Versions must start at `1` and increase by one for every step; a step, once released,
+/// must never be changed, add a new step instead.
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+impl Migration {
+    #[inline]
+    #[must_use]
+    pub const fn new(version: u32, description: &'static str, sql: &'static str) -> Self {
+        Self {
+            version,
+            description,
+            sql,
+        }
+    }
+}
+
+/// Returns the schema version currently recorded in the database.
+///
+/// # Errors
+///
+/// Will return `Err` if the `user_version` pragma cannot be read.
+#[inline]
+pub fn schema_version(conn: &Connection) -> Result<u32, Error> {
+    Ok(conn.pragma_query_value(None, "user_version", |row| row.get(0))?)
+}
+
+/// Applies every migration newer than the database's current schema version.
+///
+/// Each step is run in its own transaction together with the `user_version` bump so a
+/// failing step leaves the database at the last successfully applied version.
+///
+/// # Arguments
+/// * `conn` - The database connection to migrate.
+/// * `migrations` - All migrations for the database, ordered by version starting at `1`.
+///
+/// # Errors
+///
+/// Will return `Err` if the migrations are not numbered sequentially, the database was
+/// created by a newer version of the workflow or a migration fails to apply.
+///
+/// # Examples
+///
+/// ```
+/// use alfred_workflow::migrations::{self, Migration};
+/// use anyhow::Error;
+/// use rusqlite::Connection;
+///
+/// const MIGRATIONS: &[Migration] = &[
+///     Migration::new(
+///         1,
+///         "create config table",
+///         "CREATE TABLE IF NOT EXISTS config (
+///             key   TEXT NOT NULL PRIMARY KEY,
+///             value TEXT NOT NULL
+///         );",
+///     ),
+///     Migration::new(
+///         2,
+///         "add config updated_at",
+///         "ALTER TABLE config ADD COLUMN updated_at DATETIME;",
+///     ),
+/// ];
+///
+/// fn main() -> Result<(), Error> {
+///     let mut conn = Connection::open_in_memory()?;
+///     migrations::run(&mut conn, MIGRATIONS)?;
+///     assert_eq!(migrations::schema_version(&conn)?, 2);
+///
+///     // running again is a no-op
+///     migrations::run(&mut conn, MIGRATIONS)?;
+///
+///     // a database from a newer workflow version is refused
+///     assert!(migrations::run(&mut conn, &MIGRATIONS[..1]).is_err());
+///     Ok(())
+/// }
+/// ```
+pub fn run(conn: &mut Connection, migrations: &[Migration]) -> Result<(), Error> {
+    for (expected, migration) in (1..).zip(migrations) {
+        if migration.version != expected {
+            return Err(Error::MigrationOutOfOrder {
+                found: migration.version,
+                expected,
+            });
+        }
+    }
+
+    let current = schema_version(conn)?;
+    let latest = migrations.last().map_or(0, |m| m.version);
+    if current > latest {
+        return Err(Error::UnsupportedSchemaVersion {
+            found: current,
+            supported: latest,
+        });
+    }
+
+    for migration in migrations.iter().filter(|m| m.version > current) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration.sql)?;
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
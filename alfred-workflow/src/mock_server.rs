@@ -0,0 +1,310 @@
+//! A local HTTP server serving canned responses, for testing a workflow's refresh against
+//! recorded API fixtures instead of the real API.
+//!
+//! Enabled by the `mock-server` feature, for use from a workflow's tests:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! alfred-workflow = { workspace = true, features = ["mock-server"] }
+//! ```
+//!
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::{env, fs, process, thread};
+
+/// A canned response and the requests it answers.
+#[derive(Debug, Clone)]
+pub struct Route {
+    method: String,
+    path: String,
+    body_contains: Option<String>,
+    times: Option<usize>,
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl Route {
+    /// Answers `GET` requests for the path. A path without a query string matches any query.
+    #[inline]
+    #[must_use]
+    pub fn get(path: &str) -> Self {
+        Self::new("GET", path)
+    }
+
+    /// Answers `POST` requests for the path.
+    #[inline]
+    #[must_use]
+    pub fn post(path: &str) -> Self {
+        Self::new("POST", path)
+    }
+
+    fn new(method: &str, path: &str) -> Self {
+        Self {
+            method: method.to_owned(),
+            path: path.to_owned(),
+            body_contains: None,
+            times: None,
+            status: 200,
+            headers: Vec::new(),
+            body: String::new(),
+        }
+    }
+
+    /// Only answers requests whose body contains `text`, eg. a GraphQL cursor.
+    #[inline]
+    #[must_use]
+    pub fn body_contains(mut self, text: &str) -> Self {
+        self.body_contains = Some(text.to_owned());
+        self
+    }
+
+    /// Only answers the first `times` matching requests, after which later routes are tried.
+    #[inline]
+    #[must_use]
+    pub const fn times(mut self, times: usize) -> Self {
+        self.times = Some(times);
+        self
+    }
+
+    /// Sets the response status, `200` by default.
+    #[inline]
+    #[must_use]
+    pub const fn status(mut self, status: u16) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Adds a response header.
+    #[inline]
+    #[must_use]
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// Sets a JSON response body.
+    #[inline]
+    #[must_use]
+    pub fn json(self, body: &str) -> Self {
+        let mut route = self.header("Content-Type", "application/json");
+        body.clone_into(&mut route.body);
+        route
+    }
+
+    fn matches(&self, request: &Request) -> bool {
+        let path = if self.path.contains('?') {
+            request.target.as_str()
+        } else {
+            request.path()
+        };
+        self.method == request.method
+            && self.path == path
+            && self.times != Some(0)
+            && self
+                .body_contains
+                .as_ref()
+                .is_none_or(|text| request.body.contains(text.as_str()))
+    }
+}
+
+/// A request received by the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub method: String,
+
+    /// The path and query string.
+    pub target: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Request {
+    /// The path without the query string.
+    #[must_use]
+    pub fn path(&self) -> &str {
+        self.target.split('?').next().unwrap_or_default()
+    }
+
+    /// Returns the value of the header, ignoring the case of its name.
+    #[must_use]
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    routes: Vec<Route>,
+    requests: Vec<Request>,
+}
+
+/// A local HTTP server answering requests with the first matching route, or `404` if none
+/// matches. It stops when dropped.
+///
+/// # Examples
+///
+/// ```
+/// use alfred_workflow::mock_server::{MockServer, Route};
+///
+/// let server = MockServer::start();
+/// server.route(Route::get("/organizations").status(503).times(1));
+/// server.route(Route::get("/organizations").json(r#"[{"slug": "acme"}]"#));
+///
+/// let client = alfred_workflow::http::Client::builder().build().unwrap();
+/// let url = format!("{}/organizations?per_page=100", server.url());
+/// let body = client.send(client.get(&url)).unwrap().text().unwrap();
+///
+/// assert_eq!(body, r#"[{"slug": "acme"}]"#);
+/// assert_eq!(server.requests().len(), 2);
+/// ```
+#[derive(Debug)]
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    shutdown: Arc<AtomicBool>,
+}
+
+impl MockServer {
+    /// Starts the server on a free local port.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no local port can be bound.
+    #[must_use]
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind mock server");
+        let addr = listener.local_addr().expect("mock server has no address");
+        let state = Arc::new(Mutex::new(State::default()));
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let (server_state, server_shutdown) = (Arc::clone(&state), Arc::clone(&shutdown));
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if server_shutdown.load(Ordering::SeqCst) {
+                    break;
+                }
+                if let Ok(stream) = stream {
+                    // a client hanging up mid request is not the server's problem
+                    let _ = handle(stream, &server_state);
+                }
+            }
+        });
+        Self {
+            addr,
+            state,
+            shutdown,
+        }
+    }
+
+    /// The base URL of the server, eg. `http://127.0.0.1:49152`.
+    #[must_use]
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Adds a route, tried after those added before it.
+    pub fn route(&self, route: Route) {
+        self.lock().routes.push(route);
+    }
+
+    /// Returns every request received so far.
+    #[must_use]
+    pub fn requests(&self) -> Vec<Request> {
+        self.lock().requests.clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // wake the accept loop so it sees the flag
+        let _ = TcpStream::connect(self.addr);
+    }
+}
+
+/// Returns a path for a test's database in the temp directory, removing any left over from a
+/// previous run.
+#[must_use]
+pub fn temp_database(name: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("{name}-{}.sqlite3", process::id()));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+fn handle(stream: TcpStream, state: &Mutex<State>) -> std::io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_owned();
+    let target = parts.next().unwrap_or_default().to_owned();
+
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line)?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.push((name.trim().to_owned(), value.trim().to_owned()));
+        }
+    }
+    let mut request = Request {
+        method,
+        target,
+        headers,
+        body: String::new(),
+    };
+    let length = request
+        .header("Content-Length")
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    request.body = String::from_utf8_lossy(&body).into_owned();
+
+    let route = {
+        let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+        let route = state
+            .routes
+            .iter_mut()
+            .find(|r| r.matches(&request))
+            .map(|r| {
+                if let Some(times) = r.times.as_mut() {
+                    *times -= 1;
+                }
+                r.clone()
+            });
+        state.requests.push(request);
+        route
+    };
+    let route = route.unwrap_or_else(|| Route::get("").status(404));
+
+    let mut response = format!(
+        "HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n",
+        route.status,
+        route.body.len()
+    );
+    for (name, value) in &route.headers {
+        response.push_str(&format!("{name}: {value}\r\n"));
+    }
+    response.push_str("\r\n");
+    response.push_str(&route.body);
+
+    let mut stream = stream;
+    stream.write_all(response.as_bytes())?;
+    stream.flush()
+}
@@ -0,0 +1,170 @@
+//! Opens URLs using the platform's opener or a configured command, restricted to the hosts a
+//! workflow expects to open.
+//!
+use crate::errors::Error;
+use std::process::{Command, Stdio};
+use url::Url;
+
+/// Scheme of Alfred Preferences links, such as the workflow configuration, which are always
+/// allowed.
+const ALFRED_PREFERENCES_SCHEME: &str = "alfredpreferences";
+
+/// Options controlling how `open_url` opens a URL.
+#[derive(Debug, Clone, Default)]
+pub struct OpenOptions<'a> {
+    allowed_hosts: &'a [&'a str],
+    command: Option<&'a str>,
+    dry_run: bool,
+}
+
+impl<'a> OpenOptions<'a> {
+    /// Only `https://` URLs whose host is one of, or a subdomain of one of, `allowed_hosts`
+    /// will be opened.
+    #[inline]
+    #[must_use]
+    pub fn new(allowed_hosts: &'a [&'a str]) -> Self {
+        Self {
+            allowed_hosts,
+            ..Self::default()
+        }
+    }
+
+    /// Sets the command used instead of the platform's opener, eg. `firefox --new-tab`; the
+    /// URL is appended as its last argument.
+    #[inline]
+    #[must_use]
+    pub fn command(mut self, command: Option<&'a str>) -> Self {
+        self.command = command.filter(|c| !c.trim().is_empty());
+        self
+    }
+
+    /// Prints the command to stdout instead of running it.
+    #[inline]
+    #[must_use]
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+}
+
+/// Opens the URL, returning the command and arguments used.
+///
+/// The command is `open` on macOS, `xdg-open` elsewhere or the configured command.
+///
+/// # Errors
+///
+/// Will return `Err` if the URL is not allowed or the command cannot be run or fails.
+///
+/// # Examples
+///
+/// ```
+/// use alfred_workflow::opener::OpenOptions;
+///
+/// let options = OpenOptions::new(&["github.com"])
+///     .command(Some("firefox --new-tab"))
+///     .dry_run(true);
+///
+/// let command = alfred_workflow::open_url("https://github.com/acme/payments", &options).unwrap();
+/// assert_eq!(command, ["firefox", "--new-tab", "https://github.com/acme/payments"]);
+///
+/// assert!(alfred_workflow::open_url("https://evil.example.com", &options).is_err());
+/// assert!(alfred_workflow::open_url("http://github.com/acme/payments", &options).is_err());
+/// assert!(alfred_workflow::open_url("https://notgithub.com", &options).is_err());
+/// ```
+pub fn open_url(url: &str, options: &OpenOptions) -> Result<Vec<String>, Error> {
+    if !is_allowed(url, options.allowed_hosts) {
+        return Err(Error::UrlNotAllowed(url.to_owned()));
+    }
+
+    let mut command = match options.command {
+        Some(command) => command.split_whitespace().map(str::to_owned).collect(),
+        None => platform_command(),
+    };
+    command.push(url.to_owned());
+
+    if options.dry_run {
+        println!("{}", command.join(" "));
+        return Ok(command);
+    }
+
+    let status = Command::new(&command[0])
+        .args(&command[1..])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|source| Error::Open {
+            command: command[0].clone(),
+            source,
+        })?;
+    if !status.success() {
+        return Err(Error::OpenFailed {
+            command: command.join(" "),
+            status,
+        });
+    }
+    Ok(command)
+}
+
+/// Returns the host of the site whose API is at `api_url`, its host without an `api.` prefix,
+/// so that the workflow can open the site's pages.
+///
+/// # Examples
+///
+/// ```
+/// use alfred_workflow::opener::site_host;
+///
+/// assert_eq!(site_host("https://api.github.com").as_deref(), Some("github.com"));
+/// assert_eq!(
+///     site_host("https://github.example.com/api").as_deref(),
+///     Some("github.example.com")
+/// );
+/// assert_eq!(site_host("https://api.ddog-gov.com/api").as_deref(), Some("ddog-gov.com"));
+/// assert_eq!(site_host("not a url"), None);
+/// ```
+#[must_use]
+pub fn site_host(api_url: &str) -> Option<String> {
+    let url = Url::parse(api_url).ok()?;
+    let host = url.host_str()?;
+    Some(host.strip_prefix("api.").unwrap_or(host).to_owned())
+}
+
+fn is_allowed(url: &str, allowed_hosts: &[&str]) -> bool {
+    let Ok(url) = Url::parse(url) else {
+        return false;
+    };
+    match url.scheme() {
+        ALFRED_PREFERENCES_SCHEME => true,
+        "https" => url
+            .host_str()
+            .is_some_and(|host| allowed_hosts.iter().any(|allowed| is_within(host, allowed))),
+        _ => false,
+    }
+}
+
+/// Returns whether `host` is `domain` or one of its subdomains.
+///
+/// # Examples
+///
+/// ```
+/// use alfred_workflow::opener::is_within;
+///
+/// assert!(is_within("us5.datadoghq.com", "datadoghq.com"));
+/// assert!(is_within("github.com", "github.com"));
+/// assert!(!is_within("notgithub.com", "github.com"));
+/// ```
+#[must_use]
+pub fn is_within(host: &str, domain: &str) -> bool {
+    host == domain
+        || host
+            .strip_suffix(domain)
+            .is_some_and(|sub| sub.ends_with('.'))
+}
+
+fn platform_command() -> Vec<String> {
+    let command = if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    vec![command.to_owned()]
+}
@@ -0,0 +1,178 @@
+//! How results are printed: as Alfred items, or for shells and scripts as JSON, an aligned
+//! table or tab separated values.
+//!
+//! Alfred sets the `alfred_version` variable when it runs a workflow, so results are Alfred
+//! items then, a table in a terminal and tab separated values when piped, eg. into `fzf`.
+//!
+use crate::errors::Error;
+use crate::response::ScriptFilterResponse;
+use alfred::Item;
+use serde::Serialize;
+use serde_json::Value;
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::str::FromStr;
+
+/// Variable Alfred sets when running a workflow.
+pub const ALFRED_VARIABLE: &str = "alfred_version";
+
+/// How results are printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Script Filter JSON, for Alfred.
+    Alfred,
+
+    /// A JSON array of the rows the results show, eg. the cached repositories.
+    Json,
+
+    /// The title, subtitle and URL of each result in aligned columns.
+    Table,
+
+    /// The title, subtitle and URL of each result separated by tabs, a line per result.
+    Tsv,
+}
+
+impl Format {
+    /// Returns the format results are printed in unless one is chosen: Alfred items when
+    /// Alfred runs the workflow, a table in a terminal and tab separated values otherwise.
+    #[must_use]
+    pub fn detect() -> Self {
+        if env::var_os(ALFRED_VARIABLE).is_some() {
+            Self::Alfred
+        } else if io::stdout().is_terminal() {
+            Self::Table
+        } else {
+            Self::Tsv
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "alfred" => Ok(Self::Alfred),
+            "json" => Ok(Self::Json),
+            "table" => Ok(Self::Table),
+            "tsv" => Ok(Self::Tsv),
+            _ => Err(format!(
+                "unknown format '{s}', expected alfred, json, table or tsv"
+            )),
+        }
+    }
+}
+
+/// A result: the Alfred item showing it and the row it shows, printed by the JSON format.
+#[derive(Debug, Clone)]
+pub struct Record {
+    pub item: Item<'static>,
+    pub row: Value,
+}
+
+impl Record {
+    /// Creates the record of a row, shown by the item `item` returns for it.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the row cannot be serialized to JSON.
+    pub fn new<T, F>(row: T, item: F) -> Result<Self, Error>
+    where
+        T: Serialize,
+        F: FnOnce(T) -> Item<'static>,
+    {
+        let value = serde_json::to_value(&row)?;
+        Ok(Self {
+            item: item(row),
+            row: value,
+        })
+    }
+
+    /// The URL the result links to, its Quick Look URL.
+    #[inline]
+    #[must_use]
+    pub fn url(&self) -> &str {
+        self.item.quicklook_url.as_deref().unwrap_or_default()
+    }
+}
+
+/// Writes the records in the format.
+///
+/// # Errors
+///
+/// Will return `Err` if writing to `writer` fails.
+///
+/// # Examples
+///
+/// ```
+/// use alfred_workflow::output::{self, Format, Record};
+/// use anyhow::Error;
+/// use serde_json::json;
+///
+/// fn main() -> Result<(), Error> {
+///     let repo = json!({ "name": "acme/payments", "url": "https://github.com/acme/payments" });
+///     let records = [Record::new(repo, |repo| {
+///         alfred::ItemBuilder::new(repo["name"].as_str().unwrap_or_default().to_owned())
+///             .subtitle("payments")
+///             .quicklook_url(repo["url"].as_str().unwrap_or_default().to_owned())
+///             .into_item()
+///     })?];
+///
+///     let mut out = Vec::new();
+///     output::write(Format::Tsv, &records, &mut out)?;
+///     assert_eq!(
+///         String::from_utf8(out)?,
+///         "acme/payments\tpayments\thttps://github.com/acme/payments\n"
+///     );
+///
+///     let mut out = Vec::new();
+///     output::write(Format::Json, &records, &mut out)?;
+///     let rows: serde_json::Value = serde_json::from_slice(&out)?;
+///     assert_eq!(rows[0]["name"], "acme/payments");
+///     Ok(())
+/// }
+/// ```
+pub fn write<W: Write>(format: Format, records: &[Record], mut writer: W) -> Result<(), Error> {
+    match format {
+        Format::Alfred => {
+            return ScriptFilterResponse::new()
+                .items(records.iter().map(|record| record.item.clone()))
+                .write(writer);
+        }
+        Format::Json => {
+            let rows = records.iter().map(|record| &record.row).collect::<Vec<_>>();
+            serde_json::to_writer_pretty(&mut writer, &rows)?;
+            writeln!(writer).map_err(Error::WriteResults)?;
+        }
+        Format::Table => {
+            let rows = records.iter().map(columns).collect::<Vec<_>>();
+            let width = |i: usize| {
+                rows.iter()
+                    .map(|row: &[String; 3]| row[i].chars().count())
+                    .max()
+                    .unwrap_or_default()
+            };
+            let (title, subtitle) = (width(0), width(1));
+            for [t, s, url] in &rows {
+                let line = format!("{t:<title$}  {s:<subtitle$}  {url}");
+                writeln!(writer, "{}", line.trim_end()).map_err(Error::WriteResults)?;
+            }
+        }
+        Format::Tsv => {
+            for row in records.iter().map(columns) {
+                writeln!(writer, "{}", row.join("\t")).map_err(Error::WriteResults)?;
+            }
+        }
+    }
+    writer.flush().map_err(Error::WriteResults)
+}
+
+/// The title, subtitle and URL of a record, on one line.
+fn columns(record: &Record) -> [String; 3] {
+    let clean = |s: &str| s.replace(['\t', '\r', '\n'], " ");
+    [
+        clean(&record.item.title),
+        clean(record.item.subtitle.as_deref().unwrap_or_default()),
+        clean(record.url()),
+    ]
+}
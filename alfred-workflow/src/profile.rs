@@ -0,0 +1,75 @@
+//! Profiles, the named accounts a workflow caches, eg. a personal and a work GitHub account.
+//!
+//! Profiles are listed in the `PROFILES` variable and each is configured as described in
+//! `config::Loader::profiles`. Every cached row records the profile it was fetched with, and
+//! `@<profile>` in a query restricts the results to that profile's, see `search`. Without
+//! profiles a workflow has a single unnamed one.
+//!
+
+/// Variable listing the profiles, separated by commas or spaces, eg. `personal, work`.
+pub const VARIABLE: &str = "PROFILES";
+
+/// Returns whether `name` can name a profile: letters, digits, `-` and `_`.
+///
+/// # Examples
+///
+/// ```
+/// use alfred_workflow::profile;
+///
+/// assert!(profile::is_valid("work-eu"));
+/// assert!(!profile::is_valid("my work"));
+/// assert!(!profile::is_valid(""));
+/// ```
+#[must_use]
+pub fn is_valid(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+}
+
+/// Returns the prefix of the profile's variables, eg. `WORK_EU_` for `work-eu`.
+#[must_use]
+pub fn variable_prefix(name: &str) -> String {
+    format!("{}_", name.to_ascii_uppercase().replace('-', "_"))
+}
+
+/// Returns the subtitle of a result, naming its profile unless it is the unnamed one.
+///
+/// # Examples
+///
+/// ```
+/// use alfred_workflow::profile;
+///
+/// assert_eq!(profile::subtitle("work", "payments-api"), "work - payments-api");
+/// assert_eq!(profile::subtitle("", "payments-api"), "payments-api");
+/// assert_eq!(profile::subtitle("work", ""), "work");
+/// ```
+#[must_use]
+pub fn subtitle(profile: &str, subtitle: &str) -> String {
+    match (profile.is_empty(), subtitle.is_empty()) {
+        (true, _) => subtitle.to_owned(),
+        (false, true) => profile.to_owned(),
+        (false, false) => format!("{profile} - {subtitle}"),
+    }
+}
+
+/// Returns the uid of a result, which Alfred learns the order of, distinct for every profile
+/// but unchanged for the unnamed one.
+///
+/// # Examples
+///
+/// ```
+/// use alfred_workflow::profile;
+///
+/// assert_eq!(profile::uid("work", "acme/payments"), "work:acme/payments");
+/// assert_eq!(profile::uid("", "acme/payments"), "acme/payments");
+/// ```
+#[must_use]
+pub fn uid(profile: &str, uid: &str) -> String {
+    if profile.is_empty() {
+        uid.to_owned()
+    } else {
+        format!("{profile}:{uid}")
+    }
+}
@@ -0,0 +1,666 @@
+//! Tracks when each cached resource was last refreshed, so a stale cache can be refreshed in
+//! the background while the cached results are still shown, and how far the running refresh
+//! got, so searches can show it.
+//!
+use crate::errors::Error;
+use alfred::Item;
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OptionalExtension, ToSql};
+use serde::{Serialize, Serializer};
+use std::env;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Schema of the refreshes table, to be included in a workflow's migrations.
+pub const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS refreshes (
+        resource     TEXT    NOT NULL PRIMARY KEY,
+        refreshed_at INTEGER,
+        started_at   INTEGER,
+        failed_at    INTEGER
+    );";
+
+/// Adds how long the last refresh took and why it failed to the refreshes table, to be
+/// included in a workflow's migrations after `SCHEMA`.
+pub const OUTCOME_SCHEMA: &str = "ALTER TABLE refreshes ADD COLUMN duration_ms INTEGER;
+    ALTER TABLE refreshes ADD COLUMN error TEXT;";
+
+/// Adds the progress of the running refresh, to be included in a workflow's migrations after
+/// `OUTCOME_SCHEMA`. It holds a single row, of the resource being fetched.
+pub const PROGRESS_SCHEMA: &str = "CREATE TABLE refresh_progress (
+        id         INTEGER NOT NULL PRIMARY KEY CHECK (id = 1),
+        resource   TEXT    NOT NULL,
+        detail     TEXT    NOT NULL,
+        pages      INTEGER NOT NULL,
+        rows       INTEGER NOT NULL,
+        updated_at INTEGER NOT NULL
+    );";
+
+/// How often Alfred should re-run the Script Filter while a refresh is running.
+pub const RERUN_SECS: f64 = 1.0;
+
+/// A refresh that has not finished after this long is assumed to have died.
+const RUNNING_TIMEOUT_SECS: i64 = 10 * 60;
+
+/// A failed refresh is not retried in the background until this long after it failed.
+const RETRY_AFTER_SECS: i64 = 10 * 60;
+
+/// Appended to the database path to name the file locked while refreshing.
+const LOCK_SUFFIX: &str = ".refresh.lock";
+
+/// The state of a workflow's cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    /// Every resource was refreshed within the TTL.
+    Fresh,
+
+    /// A resource was never refreshed, or not within the TTL.
+    Stale,
+
+    /// A refresh is running.
+    Refreshing,
+
+    /// The cache is stale but the last refresh failed recently.
+    Failed,
+}
+
+/// Records that a refresh of the resources started, with no progress yet.
+///
+/// # Errors
+///
+/// Will return `Err` if the database cannot be written to.
+pub fn start(conn: &Connection, resources: &[&str]) -> Result<(), Error> {
+    conn.execute("DELETE FROM refresh_progress", [])?;
+    update(
+        conn,
+        resources,
+        "INSERT INTO refreshes (resource, started_at) VALUES (?1, ?2)
+         ON CONFLICT (resource) DO UPDATE SET started_at = excluded.started_at",
+        &[],
+    )
+}
+
+/// Records that a refresh of the resources finished successfully after `duration`.
+///
+/// # Errors
+///
+/// Will return `Err` if the database cannot be written to.
+pub fn succeed(conn: &Connection, resources: &[&str], duration: Duration) -> Result<(), Error> {
+    conn.execute("DELETE FROM refresh_progress", [])?;
+    update(
+        conn,
+        resources,
+        "INSERT INTO refreshes (resource, refreshed_at, duration_ms) VALUES (?1, ?2, ?3)
+         ON CONFLICT (resource) DO UPDATE SET refreshed_at = excluded.refreshed_at,
+            started_at = NULL, duration_ms = excluded.duration_ms, error = NULL",
+        &[&millis(duration)],
+    )
+}
+
+/// Records that a refresh of the resources failed after `duration` with the given error.
+///
+/// # Errors
+///
+/// Will return `Err` if the database cannot be written to.
+pub fn fail(
+    conn: &Connection,
+    resources: &[&str],
+    duration: Duration,
+    error: &str,
+) -> Result<(), Error> {
+    conn.execute("DELETE FROM refresh_progress", [])?;
+    update(
+        conn,
+        resources,
+        "INSERT INTO refreshes (resource, failed_at, duration_ms, error) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT (resource) DO UPDATE SET failed_at = excluded.failed_at,
+            started_at = NULL, duration_ms = excluded.duration_ms, error = excluded.error",
+        &[&millis(duration), &error],
+    )
+}
+
+/// Records that the resource was last refreshed at the given time, eg. that of an imported
+/// snapshot.
+///
+/// # Errors
+///
+/// Will return `Err` if the database cannot be written to.
+pub fn restore(
+    conn: &Connection,
+    resource: &str,
+    refreshed_at: DateTime<Utc>,
+) -> Result<(), Error> {
+    conn.execute(
+        "INSERT INTO refreshes (resource, refreshed_at) VALUES (?1, ?2)
+         ON CONFLICT (resource) DO UPDATE SET refreshed_at = excluded.refreshed_at",
+        [&resource as &dyn ToSql, &refreshed_at.timestamp()],
+    )?;
+    Ok(())
+}
+
+/// Returns the state of the cache made up of the given resources.
+///
+/// # Errors
+///
+/// Will return `Err` if the database cannot be read.
+///
+/// # Examples
+///
+/// ```
+/// use alfred_workflow::migrations::{self, Migration};
+/// use alfred_workflow::refresh::{self, State};
+/// use anyhow::Error;
+/// use rusqlite::Connection;
+/// use std::time::Duration;
+///
+/// fn main() -> Result<(), Error> {
+///     let mut conn = Connection::open_in_memory()?;
+///     migrations::run(&mut conn, &[
+///         Migration::new(1, "create refreshes table", refresh::SCHEMA),
+///         Migration::new(2, "add refresh outcomes", refresh::OUTCOME_SCHEMA),
+///         Migration::new(3, "add refresh progress", refresh::PROGRESS_SCHEMA),
+///     ])?;
+///     let ttl = Duration::from_secs(3600);
+///
+///     assert_eq!(refresh::state(&conn, &["repositories"], ttl)?, State::Stale);
+///
+///     refresh::start(&conn, &["repositories"])?;
+///     assert_eq!(refresh::state(&conn, &["repositories"], ttl)?, State::Refreshing);
+///
+///     refresh::succeed(&conn, &["repositories"], Duration::from_secs(2))?;
+///     assert_eq!(refresh::state(&conn, &["repositories"], ttl)?, State::Fresh);
+///     assert_eq!(refresh::state(&conn, &["repositories"], Duration::ZERO)?, State::Stale);
+///     Ok(())
+/// }
+/// ```
+pub fn state(conn: &Connection, resources: &[&str], ttl: Duration) -> Result<State, Error> {
+    let now = unix_now();
+    let ttl = i64::try_from(ttl.as_secs()).unwrap_or(i64::MAX);
+    let mut stale = false;
+    let mut failed = false;
+    for resource in resources {
+        let row = conn
+            .query_row(
+                "SELECT refreshed_at, started_at, failed_at FROM refreshes WHERE resource = ?1",
+                [resource],
+                |row| {
+                    Ok((
+                        row.get::<_, Option<i64>>(0)?,
+                        row.get::<_, Option<i64>>(1)?,
+                        row.get::<_, Option<i64>>(2)?,
+                    ))
+                },
+            )
+            .optional()?;
+        let (refreshed_at, started_at, failed_at) = row.unwrap_or_default();
+        if started_at.is_some_and(|t| now - t < RUNNING_TIMEOUT_SECS) {
+            return Ok(State::Refreshing);
+        }
+        stale |= refreshed_at.is_none_or(|t| now - t >= ttl);
+        failed |= failed_at.is_some_and(|t| now - t < RETRY_AFTER_SECS);
+    }
+    Ok(match (stale, failed) {
+        (false, _) => State::Fresh,
+        (true, false) => State::Stale,
+        (true, true) => State::Failed,
+    })
+}
+
+/// When a cached resource was last refreshed, and how the last refresh went.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Status {
+    pub resource: String,
+
+    /// How many rows are cached.
+    pub rows: u64,
+
+    /// When the last successful refresh finished.
+    pub refreshed_at: Option<DateTime<Utc>>,
+
+    /// When the running refresh started, if one is running.
+    pub started_at: Option<DateTime<Utc>>,
+
+    /// When the last failed refresh failed, if it failed since the last successful one.
+    pub failed_at: Option<DateTime<Utc>>,
+
+    /// How long the last refresh took, whether it succeeded or failed.
+    #[serde(rename = "duration_ms", serialize_with = "serialize_millis")]
+    pub duration: Option<Duration>,
+
+    /// Why the last refresh failed, if it failed since the last successful one.
+    pub error: Option<String>,
+}
+
+/// Serializes a duration as whole milliseconds.
+fn serialize_millis<S: Serializer>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    duration.map(millis).serialize(serializer)
+}
+
+/// Returns when each of the resources, which are tables, was last refreshed and how many rows
+/// it holds.
+///
+/// # Errors
+///
+/// Will return `Err` if the database cannot be read.
+///
+/// # Examples
+///
+/// ```
+/// use alfred_workflow::migrations::{self, Migration};
+/// use alfred_workflow::refresh;
+/// use anyhow::Error;
+/// use rusqlite::Connection;
+/// use std::time::Duration;
+///
+/// fn main() -> Result<(), Error> {
+///     let mut conn = Connection::open_in_memory()?;
+///     migrations::run(&mut conn, &[
+///         Migration::new(1, "create refreshes table", refresh::SCHEMA),
+///         Migration::new(2, "add refresh outcomes", refresh::OUTCOME_SCHEMA),
+///         Migration::new(3, "add refresh progress", refresh::PROGRESS_SCHEMA),
+///     ])?;
+///     conn.execute_batch(
+///         "CREATE TABLE pipelines (name TEXT);
+///          CREATE TABLE organizations (name TEXT);
+///          INSERT INTO pipelines (name) VALUES ('deploy'), ('test');",
+///     )?;
+///     refresh::start(&conn, &["pipelines", "organizations"])?;
+///     refresh::succeed(&conn, &["pipelines"], Duration::from_millis(1500))?;
+///     refresh::fail(&conn, &["organizations"], Duration::from_secs(3), "connection reset")?;
+///
+///     let status = refresh::status(&conn, &["pipelines", "organizations"])?;
+///     assert_eq!(status[0].rows, 2);
+///     assert!(status[0].refreshed_at.is_some());
+///     assert!(status[0].started_at.is_none());
+///     assert_eq!(status[0].duration, Some(Duration::from_millis(1500)));
+///     assert_eq!(status[1].resource, "organizations");
+///     assert!(status[1].refreshed_at.is_none());
+///     assert_eq!(status[1].error.as_deref(), Some("connection reset"));
+///     Ok(())
+/// }
+/// ```
+pub fn status(conn: &Connection, resources: &[&str]) -> Result<Vec<Status>, Error> {
+    let now = unix_now();
+    let at = |t: Option<i64>| t.and_then(|t| DateTime::from_timestamp(t, 0));
+    let mut stmt = conn.prepare(
+        "SELECT refreshed_at, started_at, failed_at, duration_ms, error
+         FROM refreshes WHERE resource = ?1",
+    )?;
+    resources
+        .iter()
+        .map(|resource| {
+            let row = stmt
+                .query_row([resource], |row| {
+                    Ok((
+                        row.get::<_, Option<i64>>(0)?,
+                        row.get::<_, Option<i64>>(1)?,
+                        row.get::<_, Option<i64>>(2)?,
+                        row.get::<_, Option<i64>>(3)?,
+                        row.get::<_, Option<String>>(4)?,
+                    ))
+                })
+                .optional()?;
+            let (refreshed_at, started_at, failed_at, duration_ms, error) = row.unwrap_or_default();
+            // a successful refresh clears the error, which caches from before it was recorded
+            // lack, so a failure the same second as a success is told apart
+            let failed_at =
+                failed_at.filter(|t| error.is_some() || refreshed_at.is_none_or(|r| *t > r));
+            let rows = conn.query_row(
+                &format!("SELECT COUNT(*) FROM main.\"{resource}\""),
+                [],
+                |row| row.get(0),
+            )?;
+            Ok(Status {
+                resource: (*resource).to_owned(),
+                rows,
+                refreshed_at: at(refreshed_at),
+                started_at: at(started_at.filter(|t| now - t < RUNNING_TIMEOUT_SECS)),
+                failed_at: at(failed_at),
+                duration: duration_ms
+                    .and_then(|ms| u64::try_from(ms).ok())
+                    .map(Duration::from_millis),
+                error: error.filter(|_| failed_at.is_some()),
+            })
+        })
+        .collect()
+}
+
+/// Returns when the least recently refreshed of the resources was refreshed, or `None` if one
+/// never was.
+///
+/// # Errors
+///
+/// Will return `Err` if the database cannot be read.
+pub fn last_refreshed(
+    conn: &Connection,
+    resources: &[&str],
+) -> Result<Option<DateTime<Utc>>, Error> {
+    let mut stmt = conn.prepare("SELECT refreshed_at FROM refreshes WHERE resource = ?1")?;
+    let mut oldest = None;
+    for resource in resources {
+        let refreshed_at = stmt
+            .query_row([resource], |row| row.get::<_, Option<i64>>(0))
+            .optional()?
+            .flatten();
+        let Some(refreshed_at) = refreshed_at else {
+            return Ok(None);
+        };
+        oldest = Some(oldest.map_or(refreshed_at, |o: i64| o.min(refreshed_at)));
+    }
+    Ok(oldest.and_then(|t| DateTime::from_timestamp(t, 0)))
+}
+
+/// Records that the running refresh fetched a page of `rows` rows of the resource.
+///
+/// # Arguments
+/// * `detail` - what the page was fetched for, eg. `org acme`, empty if nothing in particular.
+///
+/// # Errors
+///
+/// Will return `Err` if the database cannot be written to.
+pub fn report(conn: &Connection, resource: &str, detail: &str, rows: usize) -> Result<(), Error> {
+    conn.execute(
+        "INSERT INTO refresh_progress (id, resource, detail, pages, rows, updated_at)
+         VALUES (1, ?1, ?2, 1, ?3, ?4)
+         ON CONFLICT (id) DO UPDATE SET
+            pages = CASE WHEN resource = excluded.resource THEN pages + 1 ELSE 1 END,
+            rows = CASE WHEN resource = excluded.resource THEN rows + excluded.rows
+                ELSE excluded.rows END,
+            resource = excluded.resource, detail = excluded.detail,
+            updated_at = excluded.updated_at",
+        [
+            &resource as &dyn ToSql,
+            &detail,
+            &i64::try_from(rows).unwrap_or(i64::MAX),
+            &unix_now(),
+        ],
+    )?;
+    Ok(())
+}
+
+/// How far the running refresh got.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Progress {
+    /// The resource being fetched, `None` until its first page is.
+    pub resource: Option<String>,
+
+    /// What the last page was fetched for, eg. `org acme`, empty if nothing in particular.
+    pub detail: String,
+
+    /// How many pages of the resource were fetched.
+    pub pages: u64,
+
+    /// How many rows of the resource were fetched.
+    pub rows: u64,
+
+    /// When the refresh started.
+    pub started_at: DateTime<Utc>,
+
+    /// How long the refresh should still take, going by how long the last successful one took.
+    pub eta: Option<Duration>,
+}
+
+/// Returns how far the running refresh of the resources got, `None` if none is running.
+///
+/// # Errors
+///
+/// Will return `Err` if the database cannot be read.
+///
+/// # Examples
+///
+/// ```
+/// use alfred_workflow::migrations::{self, Migration};
+/// use alfred_workflow::refresh;
+/// use anyhow::Error;
+/// use rusqlite::Connection;
+/// use std::time::Duration;
+///
+/// fn main() -> Result<(), Error> {
+///     let mut conn = Connection::open_in_memory()?;
+///     migrations::run(&mut conn, &[
+///         Migration::new(1, "create refreshes table", refresh::SCHEMA),
+///         Migration::new(2, "add refresh outcomes", refresh::OUTCOME_SCHEMA),
+///         Migration::new(3, "add refresh progress", refresh::PROGRESS_SCHEMA),
+///     ])?;
+///     refresh::succeed(&conn, &["pipelines"], Duration::from_secs(600))?;
+///     assert_eq!(refresh::progress(&conn, &["pipelines"])?, None);
+///
+///     refresh::start(&conn, &["pipelines"])?;
+///     assert_eq!(refresh::progress(&conn, &["pipelines"])?.unwrap().resource, None);
+///
+///     refresh::report(&conn, "pipelines", "org acme", 100)?;
+///     refresh::report(&conn, "pipelines", "org acme", 20)?;
+///     let progress = refresh::progress(&conn, &["pipelines"])?.unwrap();
+///     assert_eq!(progress.resource.as_deref(), Some("pipelines"));
+///     assert_eq!(progress.detail, "org acme");
+///     assert_eq!((progress.pages, progress.rows), (2, 120));
+///     assert!(progress.eta.unwrap() > Duration::from_secs(590));
+///     Ok(())
+/// }
+/// ```
+pub fn progress(conn: &Connection, resources: &[&str]) -> Result<Option<Progress>, Error> {
+    let now = unix_now();
+    let mut stmt = conn.prepare(
+        "SELECT started_at, CASE WHEN error IS NULL THEN duration_ms END
+         FROM refreshes WHERE resource = ?1",
+    )?;
+    let mut started_at = None;
+    let mut last_duration_ms = None;
+    for resource in resources {
+        let Some((started, duration_ms)) = stmt
+            .query_row([resource], |row| {
+                Ok((row.get::<_, Option<i64>>(0)?, row.get::<_, Option<i64>>(1)?))
+            })
+            .optional()?
+        else {
+            continue;
+        };
+        started_at = started_at.max(started.filter(|t| now - t < RUNNING_TIMEOUT_SECS));
+        last_duration_ms = last_duration_ms.max(duration_ms);
+    }
+    let Some(started_at) = started_at else {
+        return Ok(None);
+    };
+    let row = conn
+        .query_row(
+            "SELECT resource, detail, pages, rows FROM refresh_progress",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .optional()?;
+    let (resource, detail, pages, rows) = match row {
+        Some((resource, detail, pages, rows)) => (Some(resource), detail, pages, rows),
+        None => (None, String::new(), 0, 0),
+    };
+    let eta = last_duration_ms
+        .map(|ms| ms / 1000 - (now - started_at))
+        .and_then(|secs| u64::try_from(secs).ok())
+        .map(Duration::from_secs);
+    Ok(Some(Progress {
+        resource,
+        detail,
+        pages,
+        rows,
+        started_at: DateTime::from_timestamp(started_at, 0).unwrap_or_default(),
+        eta,
+    }))
+}
+
+/// Held while a refresh runs, so only one runs at a time. Released when dropped, or by the OS
+/// should the process die, so a crashed refresh never leaves it behind.
+#[derive(Debug)]
+pub struct Lock {
+    _file: Option<File>,
+}
+
+/// Takes the refresh lock of the database, a lock on a file next to it.
+///
+/// A database without a file, such as an in-memory one, needs no lock.
+///
+/// # Errors
+///
+/// Will return `Err(Error::RefreshInProgress)` if another refresh holds the lock, or `Err` if
+/// the lock file cannot be opened.
+///
+/// # Examples
+///
+/// ```
+/// use alfred_workflow::errors::Error;
+/// use alfred_workflow::refresh;
+/// use rusqlite::Connection;
+///
+/// let path = std::env::temp_dir().join("refresh-lock-doctest.sqlite3");
+/// let conn = Connection::open(&path).unwrap();
+///
+/// let lock = refresh::lock(&conn).unwrap();
+/// assert!(matches!(refresh::lock(&conn), Err(Error::RefreshInProgress)));
+///
+/// drop(lock);
+/// assert!(refresh::lock(&conn).is_ok());
+/// ```
+pub fn lock(conn: &Connection) -> Result<Lock, Error> {
+    let Some(db) = conn.path().filter(|p| !p.is_empty()) else {
+        return Ok(Lock { _file: None });
+    };
+    let path = PathBuf::from(format!("{db}{LOCK_SUFFIX}"));
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)
+        .map_err(|source| Error::Lock {
+            path: path.clone(),
+            source,
+        })?;
+    match file.try_lock() {
+        Ok(()) => Ok(Lock { _file: Some(file) }),
+        Err(TryLockError::WouldBlock) => Err(Error::RefreshInProgress),
+        Err(TryLockError::Error(source)) => Err(Error::Lock { path, source }),
+    }
+}
+
+/// Starts a detached refresh if the cache is stale and no refresh holds the lock, returning
+/// whether one is running. A zero `ttl` disables background refreshes.
+///
+/// # Arguments
+/// * `args` - the arguments that make the running executable refresh its cache.
+///
+/// # Errors
+///
+/// Will return `Err` if the database cannot be read or the refresh cannot be started.
+///
+/// # Examples
+///
+/// ```
+/// use alfred_workflow::migrations::{self, Migration};
+/// use alfred_workflow::refresh;
+/// use anyhow::Error;
+/// use rusqlite::Connection;
+/// use std::time::Duration;
+///
+/// fn main() -> Result<(), Error> {
+///     let path = std::env::temp_dir().join("refresh-if-stale-doctest.sqlite3");
+///     let _ = std::fs::remove_file(&path);
+///     let mut conn = Connection::open(&path)?;
+///     migrations::run(&mut conn, &[
+///         Migration::new(1, "create refreshes table", refresh::SCHEMA),
+///         Migration::new(2, "add refresh outcomes", refresh::OUTCOME_SCHEMA),
+///         Migration::new(3, "add refresh progress", refresh::PROGRESS_SCHEMA),
+///     ])?;
+///
+///     // a refresh holding the lock, though not recorded as running, keeps its progress
+///     let _lock = refresh::lock(&conn)?;
+///     refresh::report(&conn, "pipelines", "org acme", 100)?;
+///     assert!(refresh::refresh_if_stale(&conn, &["pipelines"], Duration::from_secs(60), &[])?);
+///     let pages: i64 = conn.query_row("SELECT pages FROM refresh_progress", [], |row| row.get(0))?;
+///     assert_eq!(pages, 1);
+///     Ok(())
+/// }
+/// ```
+pub fn refresh_if_stale(
+    conn: &Connection,
+    resources: &[&str],
+    ttl: Duration,
+    args: &[&str],
+) -> Result<bool, Error> {
+    match state(conn, resources, ttl)? {
+        State::Refreshing => Ok(true),
+        State::Stale if !ttl.is_zero() => {
+            // a refresh running for longer than it is trusted to still holds the lock, and its
+            // progress is left alone
+            let lock = match lock(conn) {
+                Ok(lock) => lock,
+                Err(Error::RefreshInProgress) => return Ok(true),
+                Err(e) => return Err(e),
+            };
+            start(conn, resources)?;
+            // released for the spawned refresh to take, others see it started meanwhile
+            drop(lock);
+            spawn(args)?;
+            Ok(true)
+        }
+        State::Stale | State::Fresh | State::Failed => Ok(false),
+    }
+}
+
+/// The item shown after the results while a refresh is running.
+#[must_use]
+pub fn refreshing_item() -> Item<'static> {
+    alfred::ItemBuilder::new("Refreshing in background…")
+        .subtitle("Results will update once the refresh completes")
+        .valid(false)
+        .into_item()
+}
+
+/// Runs the current executable with the given arguments, detached so it outlives this
+/// process.
+fn spawn(args: &[&str]) -> Result<(), Error> {
+    let mut command = Command::new(env::current_exe().map_err(Error::Spawn)?);
+    command
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // so it is not killed along with the Script Filter when Alfred re-runs it
+        command.process_group(0);
+    }
+    command.spawn().map_err(Error::Spawn)?;
+    Ok(())
+}
+
+/// Runs the statement for each resource, with the resource as `?1`, the current time as `?2`
+/// and `params` after them.
+fn update(
+    conn: &Connection,
+    resources: &[&str],
+    sql: &str,
+    params: &[&dyn ToSql],
+) -> Result<(), Error> {
+    let now = unix_now();
+    let mut stmt = conn.prepare(sql)?;
+    for resource in resources {
+        let mut all = vec![resource as &dyn ToSql, &now];
+        all.extend_from_slice(params);
+        stmt.execute(&*all)?;
+    }
+    Ok(())
+}
+
+#[inline]
+fn millis(duration: Duration) -> i64 {
+    i64::try_from(duration.as_millis()).unwrap_or(i64::MAX)
+}
+
+#[inline]
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| i64::try_from(d.as_secs()).unwrap_or(i64::MAX))
+}
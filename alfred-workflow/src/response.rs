@@ -0,0 +1,125 @@
+//! Script Filter JSON output with the top-level fields not covered by `alfred::json`.
+//!
+//! Per-item `uid`, `mods`, `quicklookurl`, `text`, `icon` and `variables` are set using
+//! `alfred::ItemBuilder`.
+//!
+use crate::errors::Error;
+use alfred::Item;
+use serde_json::{Map, Value, json};
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// Builder for a complete Script Filter response.
+///
+/// # Examples
+///
+/// ```
+/// use alfred_workflow::response::ScriptFilterResponse;
+/// use alfred::Modifier;
+/// use anyhow::Error;
+///
+/// fn main() -> Result<(), Error> {
+///     let item = alfred::ItemBuilder::new("acme/payments")
+///         .uid("acme/payments")
+///         .arg("open https://github.com/acme/payments")
+///         .arg_mod(Modifier::Command, "open https://github.com/acme/payments/pulls")
+///         .subtitle_mod(Modifier::Command, "Open pull requests")
+///         .quicklook_url("https://github.com/acme/payments")
+///         .text_copy("https://github.com/acme/payments")
+///         .into_item();
+///
+///     let mut out = Vec::new();
+///     ScriptFilterResponse::new()
+///         .item(item)
+///         .variable("query", "pay")
+///         .rerun(1.0)
+///         .write(&mut out)?;
+///
+///     let json: serde_json::Value = serde_json::from_slice(&out)?;
+///     assert_eq!(json["rerun"], 1.0);
+///     assert_eq!(json["variables"]["query"], "pay");
+///     assert_eq!(json["items"][0]["uid"], "acme/payments");
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ScriptFilterResponse<'a> {
+    items: Vec<Item<'a>>,
+    variables: BTreeMap<String, String>,
+    rerun: Option<f64>,
+}
+
+impl<'a> ScriptFilterResponse<'a> {
+    #[inline]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends an item.
+    #[inline]
+    #[must_use]
+    pub fn item(mut self, item: Item<'a>) -> Self {
+        self.items.push(item);
+        self
+    }
+
+    /// Appends all items.
+    #[inline]
+    #[must_use]
+    pub fn items<I>(mut self, items: I) -> Self
+    where
+        I: IntoIterator<Item = Item<'a>>,
+    {
+        self.items.extend(items);
+        self
+    }
+
+    /// Sets a variable passed to the next action for whichever item is actioned.
+    #[inline]
+    #[must_use]
+    pub fn variable<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.variables.insert(key.into(), value.into());
+        self
+    }
+
+    /// Makes Alfred re-run the Script Filter after the given number of seconds, `0.1` to `5.0`.
+    #[inline]
+    #[must_use]
+    pub fn rerun(mut self, seconds: f64) -> Self {
+        self.rerun = Some(seconds.clamp(0.1, 5.0));
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    #[must_use]
+    pub fn to_json(&self) -> Value {
+        let mut root = Map::new();
+        if let Some(seconds) = self.rerun {
+            root.insert("rerun".to_owned(), json!(seconds));
+        }
+        if !self.variables.is_empty() {
+            root.insert("variables".to_owned(), json!(self.variables));
+        }
+        root.insert(
+            "items".to_owned(),
+            Value::Array(self.items.iter().map(Item::to_json).collect()),
+        );
+        Value::Object(root)
+    }
+
+    /// Writes the response as JSON to the provided writer.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if writing to `writer` fails.
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        write!(writer, "{}", self.to_json()).map_err(Error::WriteItems)?;
+        writer.flush().map_err(Error::WriteItems)
+    }
+}
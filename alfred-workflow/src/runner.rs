@@ -0,0 +1,569 @@
+//! The standard command line of a `Workflow`:
+//!
+//! - `<query>...` searches the cache, starting with one of the workflow's modes if it has any,
+//!   and refreshes it in the background once stale, showing its progress until it finishes.
+//!   An `@profile` may come before the mode. Script Filters should run `-- {query}`, so a
+//!   query starting with a command's name, eg. `status page`, is searched for; one that is not
+//!   a valid command line is searched for anyway, see `get_matches`.
+//! - `new [days] [query]...` lists what first appeared in the cache in the last few days, if
+//!   the workflow tracks changes.
+//! - `refresh` refreshes the cache, printing what changed.
+//! - `open <url>` opens a result, ranking it higher next time.
+//! - `config check` prints the effective configuration.
+//! - `status` shows how many rows each resource holds, when it was last refreshed, how long
+//!   that took and why the last refresh failed, if it did.
+//! - `export <file>` and `import <file>` save the cache to a snapshot and load one, so it can
+//!   be shared; `-` is stdout or stdin.
+//!
+//! Searches and `status` are Script Filters, printing Alfred items, and show failures as items
+//! too. Searches of a cache older than the workflow's `stale_after` end with an item
+//! suggesting to refresh it.
+//!
+//! `--format json|table|tsv` prints their results for shells and scripts instead, see
+//! `output`; what Alfred shows besides the results, eg. a refresh's progress, is printed to
+//! stderr and failures are returned.
+//!
+use crate::changes::NewRow;
+use crate::config::WorkflowConfig;
+use crate::failure::Failure;
+use crate::opener::OpenOptions;
+use crate::output::{self, Record};
+use crate::refresh::{self, Progress, Status};
+use crate::response::ScriptFilterResponse;
+use crate::search::SearchQuery;
+use crate::snapshot::{Format, ImportMode};
+use crate::workflow::{Mode, Workflow};
+use crate::{frecency, logging};
+use alfred::Item;
+use anyhow::{Context, Error};
+use chrono::{DateTime, TimeDelta, Utc};
+use clap::error::ErrorKind;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{env, io};
+
+const SUBCOMMAND_REFRESH: &str = "refresh";
+
+/// Describes refreshing, in the item suggesting it.
+const REFRESH_ABOUT: &str = "Refresh Cache, be patient you will be notified once complete";
+
+/// Lists what first appeared recently, in workflows tracking changes.
+const MODE_NEW: Mode = Mode::new(
+    "new",
+    "List what first appeared in the last 7 days, new <days> for another number",
+);
+
+/// How many days back `new` looks by default.
+const NEW_DAYS: i64 = 7;
+
+/// The most rows `new` lists.
+const NEW_LIMIT: usize = 50;
+
+/// The path meaning stdout or stdin instead of a file.
+const STDIO_PATH: &str = "-";
+
+#[derive(Parser)]
+#[command(propagate_version = true)]
+struct Cli {
+    /// Searches the cache, the first word choosing the kind of search if there are several
+    #[arg(allow_hyphen_values = true)]
+    query: Option<Vec<String>>,
+
+    /// How results are printed: alfred, json, table or tsv; Alfred items when run by Alfred, a
+    /// table in a terminal and tsv otherwise
+    #[arg(long, global = true)]
+    format: Option<output::Format>,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Refreshes the cache
+    Refresh,
+    /// Opens the URL, if its host is allowed
+    Open {
+        #[arg(required_unless_present = "input")]
+        url: Option<String>,
+
+        /// The URL, as `open --input <url>` of the Datadog workflow's older configurations
+        #[arg(long, hide = true, conflicts_with = "url")]
+        input: Option<String>,
+
+        /// Prints the command that would open the URL instead of running it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Workflow configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+    /// Refreshes the cache, as `refresh`
+    #[command(hide = true)]
+    Settings {
+        #[command(subcommand)]
+        command: SettingsCommands,
+    },
+    /// Shows the rows, last refresh, its duration and last error of each cached resource
+    Status,
+    /// Saves the cache to a JSON or NDJSON snapshot
+    Export {
+        /// The snapshot file, or - for stdout
+        path: PathBuf,
+
+        /// json or ndjson, guessed from the file extension by default
+        #[arg(long)]
+        format: Option<Format>,
+    },
+    /// Loads a snapshot saved by export into the cache, merging it by default
+    Import {
+        /// The snapshot file, or - for stdin
+        path: PathBuf,
+
+        /// json or ndjson, guessed from the file extension by default
+        #[arg(long)]
+        format: Option<Format>,
+
+        /// Replaces the cached rows instead of merging the snapshot into them
+        #[arg(long)]
+        replace: bool,
+    },
+}
+
+/// `settings refresh`, as the Datadog workflow's older configurations refresh.
+#[derive(Subcommand)]
+enum SettingsCommands {
+    Refresh,
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Prints the effective configuration, secrets redacted, and any problems with it
+    Check,
+}
+
+/// Runs the workflow's command line, see the module documentation.
+///
+/// # Arguments
+/// * `name` - the executable's name, eg. `env!("CARGO_PKG_NAME")`.
+/// * `version` - the executable's version, eg. `env!("CARGO_PKG_VERSION")`.
+///
+/// # Errors
+///
+/// Will return `Err` if a command other than a Script Filter fails.
+pub fn run<W: Workflow>(name: &'static str, version: &'static str) -> Result<(), Error> {
+    logging::init(W::Config::NAME);
+    let command = Cli::command().name(name).version(version);
+    let cli = Cli::from_arg_matches(&get_matches(command))?;
+    let script_filter = matches!(cli.command, None | Some(Commands::Status));
+    let format = cli.format.unwrap_or_else(output::Format::detect);
+
+    dispatch::<W>(cli, format).or_else(|err| {
+        let failure = Failure::classify::<W::Error>(&err);
+        if script_filter && format != output::Format::Alfred {
+            Err(err)
+        } else if script_filter {
+            ScriptFilterResponse::new()
+                .item(failure.into_item(SUBCOMMAND_REFRESH))
+                .write(io::stdout())?;
+            Ok(())
+        } else {
+            println!("{failure}");
+            Err(err)
+        }
+    })
+}
+
+/// Parses the command line, or if it is not a valid one parses it as a search instead, eg.
+/// `status page` typed in Alfred by a Script Filter not running `-- {query}`.
+///
+/// The search starts at the first argument naming a subcommand, options before it are kept.
+/// Help, the version and the command line's error are printed, exiting, if it is not a valid
+/// search either.
+#[must_use]
+pub fn get_matches(command: clap::Command) -> clap::ArgMatches {
+    let args = env::args_os().collect::<Vec<_>>();
+    let err = match command.clone().try_get_matches_from(&args) {
+        Ok(matches) => return matches,
+        Err(err) => err,
+    };
+    if matches!(
+        err.kind(),
+        ErrorKind::DisplayHelp
+            | ErrorKind::DisplayVersion
+            | ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand
+    ) {
+        err.exit();
+    }
+    let subcommand = args.iter().skip(1).position(|arg| {
+        command
+            .get_subcommands()
+            .any(|sub| arg.to_str() == Some(sub.get_name()))
+    });
+    let Some(at) = subcommand else {
+        err.exit();
+    };
+    let mut search = args;
+    search.insert(at + 1, "--".into());
+    command
+        .try_get_matches_from(search)
+        .unwrap_or_else(|_| err.exit())
+}
+
+fn dispatch<W: Workflow>(cli: Cli, format: output::Format) -> Result<(), Error> {
+    match &cli.command {
+        Some(Commands::Config {
+            command: ConfigCommands::Check,
+        }) => {
+            let (_, loader) = W::Config::check()?;
+            print!("{loader}");
+            return Ok(loader.validate()?);
+        }
+        Some(Commands::Open {
+            url,
+            input,
+            dry_run,
+        }) => {
+            let url = url.as_deref().or(input.as_deref()).unwrap_or_default();
+            // eg. the workflow configuration, which is not a search result and opens even while
+            // the configuration it fixes is invalid
+            if !url.starts_with("https://") {
+                let (config, _) = W::Config::check()?;
+                return open::<W>(&config, url, &[], *dry_run);
+            }
+        }
+        _ => {}
+    }
+
+    // a snapshot can be shared by those without the API credentials
+    let config = match &cli.command {
+        Some(Commands::Export { .. } | Commands::Import { .. }) => W::Config::check()?.0,
+        _ => W::Config::load()?,
+    };
+    if let Some(Commands::Open {
+        url,
+        input,
+        dry_run,
+    }) = &cli.command
+    {
+        let url = url.as_deref().or(input.as_deref()).unwrap_or_default();
+        open::<W>(&config, url, &W::allowed_hosts(&config), *dry_run)?;
+        if *dry_run {
+            return Ok(());
+        }
+    }
+    let cache_ttl = W::cache_ttl(&config);
+    let stale_after = W::stale_after(&config);
+    let mut wf = W::from_config(config)?;
+
+    match cli.command {
+        Some(
+            Commands::Refresh
+            | Commands::Settings {
+                command: SettingsCommands::Refresh,
+            },
+        ) => {
+            let summary = wf.refresh()?;
+            println!("Successfully Refreshed {} cache: {summary}", W::SERVICE);
+        }
+        Some(Commands::Config { .. }) => {}
+        Some(Commands::Open { url, input, .. }) => {
+            let url = url.or(input).unwrap_or_default();
+            let query = env::var(frecency::QUERY_VARIABLE).unwrap_or_default();
+            wf.record_open(&url, &query)?;
+        }
+        Some(Commands::Export { path, format }) => {
+            let format = format.unwrap_or_else(|| Format::from_path(&path));
+            let rows = if path == Path::new(STDIO_PATH) {
+                wf.export(format, io::stdout().lock())?
+            } else {
+                let file = File::create(&path)
+                    .with_context(|| format!("failed to create {}", path.display()))?;
+                wf.export(format, BufWriter::new(file))?
+            };
+            // stdout may be the snapshot itself
+            eprintln!("Exported {rows} rows of {} cache", W::SERVICE);
+        }
+        Some(Commands::Import {
+            path,
+            format,
+            replace,
+        }) => {
+            let format = format.unwrap_or_else(|| Format::from_path(&path));
+            let mode = if replace {
+                ImportMode::Replace
+            } else {
+                ImportMode::Merge
+            };
+            let rows = if path == Path::new(STDIO_PATH) {
+                wf.import(format, mode, io::stdin().lock())?
+            } else {
+                let file = File::open(&path)
+                    .with_context(|| format!("failed to open {}", path.display()))?;
+                wf.import(format, mode, BufReader::new(file))?
+            };
+            println!("Imported {rows} rows into {} cache", W::SERVICE);
+        }
+        Some(Commands::Status) => {
+            let records = wf
+                .status()?
+                .into_iter()
+                .map(|status| Record::new(status, status_item))
+                .collect::<Result<Vec<_>, _>>()?;
+            output::write(format, &records, io::stdout())?;
+        }
+        None => match cli.query {
+            Some(words) => {
+                let (mode, query) = split_mode(&modes::<W>(), &words);
+                let query = query.trim();
+
+                let refreshing = wf.refresh_if_stale(cache_ttl, &[SUBCOMMAND_REFRESH])?;
+                let progress = if refreshing { wf.progress()? } else { None };
+                let records = if mode == Some(MODE_NEW.name) {
+                    new_records(&wf, query)?
+                } else {
+                    wf.query(mode, query)?
+                };
+                if format != output::Format::Alfred {
+                    // stdout is only the results, to be read by scripts
+                    if let Some(progress) = &progress {
+                        eprintln!("{}", progress_title(W::SERVICE, progress));
+                    } else if refreshing {
+                        eprintln!("Refreshing {} cache in the background", W::SERVICE);
+                    } else if let Some(age) = stale_age(&wf, stale_after)? {
+                        eprintln!(
+                            "{} cache is {age} old, run refresh to refresh it",
+                            W::SERVICE
+                        );
+                    }
+                    output::write(format, &records, io::stdout())?;
+                    return Ok(());
+                }
+                let mut response = ScriptFilterResponse::new();
+                if let Some(progress) = &progress {
+                    response = response.item(progress_item(W::SERVICE, progress));
+                }
+                response = response
+                    .items(records.into_iter().map(|record| record.item))
+                    .variable(frecency::QUERY_VARIABLE, query);
+                if refreshing {
+                    if progress.is_none() {
+                        response = response.item(refresh::refreshing_item());
+                    }
+                    response = response.rerun(refresh::RERUN_SECS);
+                } else if let Some(age) = stale_age(&wf, stale_after)? {
+                    response = response.item(stale_item(&age));
+                }
+                response.write(io::stdout())?;
+            }
+            None => {
+                let mut records = modes::<W>()
+                    .into_iter()
+                    .map(|mode| {
+                        Record::new(mode, |mode| {
+                            alfred::ItemBuilder::new(mode.name)
+                                .subtitle(mode.about)
+                                .autocomplete(format!("{} ", mode.name))
+                                .valid(false)
+                                .into_item()
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                let refresh = Mode::new(SUBCOMMAND_REFRESH, REFRESH_ABOUT);
+                records.push(Record::new(refresh, |refresh| {
+                    alfred::ItemBuilder::new(refresh.name)
+                        .subtitle(refresh.about)
+                        .arg(SUBCOMMAND_REFRESH)
+                        .into_item()
+                })?);
+                output::write(format, &records, io::stdout())?;
+            }
+        },
+    }
+    Ok(())
+}
+
+/// Opens the URL if its host is one of `hosts`, with the configured command.
+fn open<W: Workflow>(
+    config: &W::Config,
+    url: &str,
+    hosts: &[String],
+    dry_run: bool,
+) -> Result<(), Error> {
+    let hosts = hosts.iter().map(String::as_str).collect::<Vec<_>>();
+    let options = OpenOptions::new(&hosts)
+        .command(W::open_command(config))
+        .dry_run(dry_run);
+    crate::open_url(url, &options)?;
+    Ok(())
+}
+
+/// Returns the workflow's modes, followed by `new` if it tracks changes.
+fn modes<W: Workflow>() -> Vec<Mode> {
+    let mut modes = W::MODES.to_vec();
+    if !W::TRACKED.is_empty() {
+        modes.push(MODE_NEW);
+    }
+    modes
+}
+
+/// Lists the rows first seen in the last few days, the query's first word if it is a number of
+/// days, matching the rest of the query.
+fn new_records<W: Workflow>(wf: &W, query: &str) -> Result<Vec<Record>, W::Error> {
+    let (first, rest) = query.split_once(' ').unwrap_or((query, ""));
+    let (days, query) = match first.parse::<i64>() {
+        Ok(days) if days > 0 => (days, rest),
+        _ => (NEW_DAYS, query),
+    };
+    let now = Utc::now();
+    let since = TimeDelta::try_days(days)
+        .and_then(|days| now.checked_sub_signed(days))
+        .unwrap_or(DateTime::<Utc>::MIN_UTC);
+    let rows = wf.first_seen_since(since, &SearchQuery::parse(query))?;
+    Ok(rows
+        .into_iter()
+        .take(NEW_LIMIT)
+        .map(|row| Record::new(row, |row| new_item(now, row)))
+        .collect::<Result<_, crate::errors::Error>>()?)
+}
+
+/// Shows a row first seen recently, eg. `timeboard first seen 3 hours ago`.
+fn new_item(now: DateTime<Utc>, row: NewRow) -> Item<'static> {
+    let subtitle = format!("{} first seen {}", row.noun, ago(now, row.first_seen));
+    alfred::ItemBuilder::new(row.title.clone())
+        .subtitle(crate::profile::subtitle(&row.profile, &subtitle))
+        .arg(format!("open {}", row.url))
+        .quicklook_url(row.url.clone())
+        .text_copy(row.url)
+        .text_large_type(row.title)
+        .into_item()
+}
+
+/// Splits the mode out of the query's words: the first word, or the second after an
+/// `@profile`, which stays in the query.
+fn split_mode<'a>(modes: &[Mode], words: &'a [String]) -> (Option<&'a str>, String) {
+    let at = usize::from(words.first().is_some_and(|word| word.starts_with('@')));
+    match words.get(at) {
+        Some(word) if modes.iter().any(|m| m.name == word) => {
+            let rest = words[..at].iter().chain(&words[at + 1..]);
+            let query = rest.map(String::as_str).collect::<Vec<_>>().join(" ");
+            (Some(word.as_str()), query)
+        }
+        _ => (None, words.join(" ")),
+    }
+}
+
+fn status_item(status: Status) -> Item<'static> {
+    let now = Utc::now();
+    let rows = format!(
+        "{} row{}",
+        status.rows,
+        if status.rows == 1 { "" } else { "s" }
+    );
+    let mut subtitle = match (status.started_at, status.refreshed_at) {
+        (Some(started_at), _) => format!("{rows}, refreshing, started {}", ago(now, started_at)),
+        (None, Some(refreshed_at)) => format!("{rows}, refreshed {}", ago(now, refreshed_at)),
+        (None, None) => format!("{rows}, never refreshed"),
+    };
+    if let Some(duration) = status.duration {
+        subtitle.push_str(&format!(
+            ", last refresh took {:.1}s",
+            duration.as_secs_f64()
+        ));
+    }
+    if let Some(failed_at) = status.failed_at {
+        subtitle.push_str(&format!(", failed {}", ago(now, failed_at)));
+        if let Some(error) = &status.error {
+            subtitle.push_str(&format!(": {error}"));
+        }
+    }
+    alfred::ItemBuilder::new(status.resource)
+        .subtitle(subtitle.clone())
+        .arg(SUBCOMMAND_REFRESH)
+        .text_large_type(status.error.unwrap_or(subtitle))
+        .into_item()
+}
+
+/// Describes how far the running refresh got, eg. `Refreshing Buildkite: org acme, 420
+/// pipelines so far`.
+fn progress_title(service: &str, progress: &Progress) -> String {
+    match &progress.resource {
+        Some(resource) if progress.detail.is_empty() => {
+            format!("Refreshing {service}: {} {resource} so far", progress.rows)
+        }
+        Some(resource) => format!(
+            "Refreshing {service}: {}, {} {resource} so far",
+            progress.detail, progress.rows
+        ),
+        None => format!("Refreshing {service}…"),
+    }
+}
+
+/// Shows how far the running refresh got, along with how long it should still take.
+fn progress_item(service: &str, progress: &Progress) -> Item<'static> {
+    let now = Utc::now();
+    let pages = format!(
+        "{} page{} fetched",
+        progress.pages,
+        if progress.pages == 1 { "" } else { "s" }
+    );
+    let started = format!("started {}", ago(now, progress.started_at));
+    let subtitle = match progress.eta.and_then(|eta| TimeDelta::from_std(eta).ok()) {
+        Some(eta) if !eta.is_zero() => {
+            format!("{pages}, {started}, about {} left", age(now + eta, now))
+        }
+        _ => format!("{pages}, {started}"),
+    };
+    alfred::ItemBuilder::new(progress_title(service, progress))
+        .subtitle(subtitle)
+        .valid(false)
+        .into_item()
+}
+
+/// Returns how old the cache is if it is older than `stale_after`, unless that is zero.
+fn stale_age<W: Workflow>(wf: &W, stale_after: Duration) -> Result<Option<String>, W::Error> {
+    if stale_after.is_zero() {
+        return Ok(None);
+    }
+    let now = Utc::now();
+    Ok(wf.last_refreshed()?.and_then(|refreshed_at| {
+        let stale = (now - refreshed_at)
+            .to_std()
+            .is_ok_and(|age| age > stale_after);
+        stale.then(|| age(now, refreshed_at))
+    }))
+}
+
+/// Suggests refreshing the cache, which was last refreshed `age` ago.
+fn stale_item(age: &str) -> Item<'static> {
+    alfred::ItemBuilder::new(format!("Cache {age} old — ↵ to refresh"))
+        .subtitle(REFRESH_ABOUT)
+        .arg(SUBCOMMAND_REFRESH)
+        .into_item()
+}
+
+/// Describes how long ago `then` was, eg. `3 hours ago`.
+fn ago(now: DateTime<Utc>, then: DateTime<Utc>) -> String {
+    if now - then < TimeDelta::minutes(1) {
+        return "just now".to_owned();
+    }
+    format!("{} ago", age(now, then))
+}
+
+/// Describes how long it has been since `then`, eg. `3 days`.
+fn age(now: DateTime<Utc>, then: DateTime<Utc>) -> String {
+    let secs = (now - then).num_seconds().max(0);
+    let (n, unit) = match secs {
+        0..60 => (secs, "second"),
+        60..3_600 => (secs / 60, "minute"),
+        3_600..86_400 => (secs / 3_600, "hour"),
+        _ => (secs / 86_400, "day"),
+    };
+    let plural = if n == 1 { "" } else { "s" };
+    format!("{n} {unit}{plural}")
+}
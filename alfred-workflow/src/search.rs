@@ -0,0 +1,369 @@
+//! Structured search queries that compile to parameterized `SQLite` filters.
+//!
+//! Supported syntax:
+//! * `api payments` - every term must fuzzy match, in any order.
+//! * `"payments api"` - quoted phrases must match as written.
+//! * `-archived` - negated terms must not match.
+//! * `owner:acme` - qualified terms match against the column registered for the field.
+//! * `@work` - only rows of the `work` profile match, see `profile`.
+//!
+use crate::fuzzy;
+use std::fmt::Write;
+
+/// A single parsed term of a `SearchQuery`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Term {
+    pub field: Option<String>,
+    pub value: String,
+    pub negated: bool,
+    pub phrase: bool,
+}
+
+/// Describes which columns a `SearchQuery` is matched against.
+#[derive(Debug, Clone, Copy)]
+pub struct SearchFields<'a> {
+    /// Columns unqualified terms are matched against; a term matches if any column matches.
+    pub default: &'a [&'a str],
+    /// `field:value` qualifiers mapped to the column or SQL expression they match against.
+    pub qualifiers: &'a [(&'a str, &'a str)],
+    /// The column holding the profile of each row, which `@profile` matches, if any.
+    pub profile: Option<&'a str>,
+}
+
+/// A compiled SQL `WHERE` clause and its positional parameters, in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SqlFilter {
+    pub clause: String,
+    pub params: Vec<String>,
+}
+
+/// A parsed search query.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchQuery {
+    terms: Vec<Term>,
+    profile: Option<String>,
+}
+
+impl SearchQuery {
+    /// Parses the raw text typed by the user.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use alfred_workflow::search::SearchQuery;
+    ///
+    /// let query = SearchQuery::parse(r#"@work payments "web api" -legacy owner:acme"#);
+    /// let terms = query.terms();
+    /// assert_eq!(terms.len(), 4);
+    /// assert!(terms[1].phrase);
+    /// assert!(terms[2].negated);
+    /// assert_eq!(terms[3].field.as_deref(), Some("owner"));
+    /// assert_eq!(query.profile(), Some("work"));
+    /// ```
+    #[must_use]
+    pub fn parse(input: &str) -> Self {
+        let mut terms = Vec::new();
+        let mut profile = None;
+        let mut chars = input.chars().peekable();
+
+        loop {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            if chars.peek().is_none() {
+                break;
+            }
+
+            let mut raw = String::new();
+            let mut phrase = false;
+            let mut in_quotes = false;
+            while let Some(c) = chars.next_if(|c| in_quotes || !c.is_whitespace()) {
+                if c == '"' {
+                    in_quotes = !in_quotes;
+                    phrase = true;
+                } else {
+                    raw.push(c);
+                }
+            }
+
+            if let Some(name) = raw.strip_prefix('@').filter(|_| !phrase)
+                && crate::profile::is_valid(name)
+            {
+                profile = Some(name.to_owned());
+                continue;
+            }
+            let (negated, raw) = match raw.strip_prefix('-') {
+                Some(rest) if !rest.is_empty() => (true, rest),
+                _ => (false, raw.as_str()),
+            };
+            let (field, value) = match raw.split_once(':') {
+                Some((field, value))
+                    if !field.is_empty()
+                        && !value.is_empty()
+                        && field.chars().all(char::is_alphanumeric) =>
+                {
+                    (Some(field.to_lowercase()), value)
+                }
+                _ => (None, raw),
+            };
+            if value.is_empty() || value == "-" {
+                continue;
+            }
+            terms.push(Term {
+                field,
+                value: value.to_owned(),
+                negated,
+                phrase,
+            });
+        }
+        Self { terms, profile }
+    }
+
+    /// Creates the query of the terms, without a profile.
+    pub(crate) fn from_terms(terms: Vec<Term>) -> Self {
+        Self {
+            terms,
+            profile: None,
+        }
+    }
+
+    /// Adds a qualified term to the query, eg. a value supplied using a dedicated CLI option.
+    #[must_use]
+    pub fn and(mut self, field: &str, value: &str) -> Self {
+        self.terms.push(Term {
+            field: Some(field.to_lowercase()),
+            value: value.to_owned(),
+            negated: false,
+            phrase: false,
+        });
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn terms(&self) -> &[Term] {
+        &self.terms
+    }
+
+    /// The profile the results are restricted to, if any.
+    #[inline]
+    #[must_use]
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+
+    #[inline]
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty() && self.profile.is_none()
+    }
+
+    /// Splits the query into its text, the plain and phrase terms including those with a
+    /// qualifier not registered in `fields`, and the rest: its negated and qualified terms and
+    /// its profile.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use alfred_workflow::search::{SearchFields, SearchQuery};
+    ///
+    /// const FIELDS: SearchFields = SearchFields {
+    ///     default: &["name"],
+    ///     qualifiers: &[("owner", "owner")],
+    ///     profile: None,
+    /// };
+    ///
+    /// let (text, rest) = SearchQuery::parse("api team:web -legacy owner:acme @work").partition_text(&FIELDS);
+    /// let text = text.iter().map(|t| t.value.as_str()).collect::<Vec<_>>();
+    /// assert_eq!(text, ["api", "team:web"]);
+    /// assert_eq!(rest.terms().len(), 2);
+    /// assert_eq!(rest.profile(), Some("work"));
+    /// ```
+    #[must_use]
+    pub fn partition_text(&self, fields: &SearchFields) -> (Vec<Term>, SearchQuery) {
+        let mut text = Vec::new();
+        let mut rest = Vec::new();
+        for term in &self.terms {
+            let registered = term
+                .field
+                .as_deref()
+                .is_some_and(|field| fields.qualifiers.iter().any(|(name, _)| *name == field));
+            if term.negated || registered {
+                rest.push(term.clone());
+            } else if let Some(field) = &term.field {
+                text.push(Term {
+                    field: None,
+                    value: format!("{field}:{}", term.value),
+                    ..term.clone()
+                });
+            } else {
+                text.push(term.clone());
+            }
+        }
+        let rest = SearchQuery {
+            terms: rest,
+            profile: self.profile.clone(),
+        };
+        (text, rest)
+    }
+
+    /// Compiles the query into a parameterized SQL filter.
+    ///
+    /// Terms are `AND`ed together; a qualifier not registered in `fields` is matched as a
+    /// plain term including the `field:` prefix. The profile must match exactly, if `fields`
+    /// has its column. An empty query matches everything.
+    ///
+    /// Plain terms match when their characters appear in order, the same rule the fuzzy
+    /// scorer uses, so the results should be ranked using `score`. Phrases, negated and
+    /// qualified terms match as substrings.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use alfred_workflow::search::{SearchFields, SearchQuery};
+    ///
+    /// const FIELDS: SearchFields = SearchFields {
+    ///     default: &["name"],
+    ///     qualifiers: &[("owner", "owner")],
+    ///     profile: Some("profile"),
+    /// };
+    ///
+    /// let filter = SearchQuery::parse(r#"pay-api "v2" -legacy owner:acme"#).to_sql(&FIELDS);
+    /// assert_eq!(
+    ///     filter.clause,
+    ///     r"name LIKE ? ESCAPE '\' AND name LIKE ? ESCAPE '\' AND NOT name LIKE ? ESCAPE '\' AND owner LIKE ? ESCAPE '\'"
+    /// );
+    /// assert_eq!(filter.params, ["%p%a%y%a%p%i%", "%v2%", "%legacy%", "%acme%"]);
+    ///
+    /// let filter = SearchQuery::parse("@work").to_sql(&FIELDS);
+    /// assert_eq!(filter.clause, "profile = ?");
+    /// assert_eq!(filter.params, ["work"]);
+    /// ```
+    #[must_use]
+    pub fn to_sql(&self, fields: &SearchFields) -> SqlFilter {
+        let mut clauses = Vec::with_capacity(self.terms.len() + 1);
+        let mut params = Vec::new();
+        if let (Some(profile), Some(column)) = (&self.profile, fields.profile) {
+            clauses.push(format!("{column} = ?"));
+            params.push(profile.clone());
+        }
+        for term in &self.terms {
+            let qualifier = term.field.as_deref().and_then(|field| {
+                fields
+                    .qualifiers
+                    .iter()
+                    .find(|(name, _)| *name == field)
+                    .map(|(_, column)| column)
+            });
+            let (columns, pattern) = match (qualifier, &term.field) {
+                (Some(column), _) => (std::slice::from_ref(column), like_pattern(term)),
+                (None, Some(field)) => (
+                    fields.default,
+                    like_pattern(&Term {
+                        value: format!("{field}:{}", term.value),
+                        ..term.clone()
+                    }),
+                ),
+                (None, None) => (fields.default, like_pattern(term)),
+            };
+
+            let mut clause = String::new();
+            if term.negated {
+                clause.push_str("NOT ");
+            }
+            if columns.len() > 1 {
+                clause.push('(');
+            }
+            for (i, column) in columns.iter().enumerate() {
+                if i > 0 {
+                    clause.push_str(" OR ");
+                }
+                let _ = write!(clause, r"{column} LIKE ? ESCAPE '\'");
+                params.push(pattern.clone());
+            }
+            if columns.len() > 1 {
+                clause.push(')');
+            }
+            clauses.push(clause);
+        }
+
+        if clauses.is_empty() {
+            clauses.push("1".to_owned());
+        }
+        SqlFilter {
+            clause: clauses.join(" AND "),
+            params,
+        }
+    }
+
+    /// Fuzzy scores `candidate` against the plain and phrase terms of the query.
+    ///
+    /// Returns `None` if any of those terms does not match; negated and qualified terms are
+    /// left to the SQL filter and do not contribute.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use alfred_workflow::search::SearchQuery;
+    ///
+    /// let query = SearchQuery::parse("api payments");
+    /// assert_eq!(query.score("payments-api"), SearchQuery::parse("payments api").score("payments-api"));
+    /// assert!(query.score("payments-api").unwrap() > query.score("rapid-payment-systems").unwrap());
+    /// assert_eq!(query.score("payments"), None);
+    /// ```
+    #[must_use]
+    pub fn score(&self, candidate: &str) -> Option<i64> {
+        self.terms
+            .iter()
+            .filter(|t| t.field.is_none() && !t.negated)
+            .map(|t| {
+                if t.phrase {
+                    fuzzy::score(&t.value, candidate)
+                } else {
+                    fuzzy::score(&strip_separators(&t.value), candidate)
+                }
+            })
+            .sum()
+    }
+}
+
+#[inline]
+fn strip_separators(value: &str) -> String {
+    value.chars().filter(|c| !matches!(c, '_' | '-')).collect()
+}
+
+/// Builds the `LIKE` pattern for a term; unquoted terms also allow anything in place of `_`
+/// and `-` so `foo-bar` matches `foo_bar` and `foobar`.
+fn like_pattern(term: &Term) -> String {
+    let mut pattern = String::from("%");
+    if term.phrase {
+        pattern.push_str(&escape_like(&term.value));
+    } else if term.field.is_none() && !term.negated {
+        let chars = strip_separators(&term.value)
+            .chars()
+            .map(|c| escape_like(c.encode_utf8(&mut [0; 4])))
+            .collect::<Vec<_>>();
+        pattern.push_str(&chars.join("%"));
+    } else {
+        let parts = term
+            .value
+            .split(['_', '-'])
+            .filter(|s| !s.is_empty())
+            .map(escape_like)
+            .collect::<Vec<_>>();
+        pattern.push_str(&parts.join("%"));
+    }
+    if pattern.len() > 1 {
+        pattern.push('%');
+    }
+    pattern
+}
+
+fn escape_like(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
@@ -0,0 +1,202 @@
+//! Secrets such as API tokens, which the workflow configuration can point to instead of
+//! holding, so they stay out of exported workflows and dotfile repositories.
+//!
+//! A secret is configured as one of:
+//! - `env:NAME`, the value of the environment variable `NAME`.
+//! - `file:/path/to/token`, the contents of a file which only its owner may read. A leading
+//!   `~/` is the home directory.
+//! - `cmd:pass show github/token`, the output of a command run by `sh -c`.
+//! - anything else, which is the secret itself.
+//!
+//! Surrounding whitespace, such as a trailing newline, is removed. A secret is only resolved
+//! when first used, so eg. a password manager is not prompted on every keystroke in Alfred,
+//! and is then cached for the rest of the process.
+//!
+use crate::errors::Error;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::{Mutex, PoisonError};
+use std::{fmt, fs};
+
+const ENV_PREFIX: &str = "env:";
+const FILE_PREFIX: &str = "file:";
+const CMD_PREFIX: &str = "cmd:";
+
+/// Secrets resolved so far, by their configured reference.
+static RESOLVED: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+/// A configured secret, resolved on first use.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret {
+    reference: String,
+}
+
+impl Secret {
+    /// Creates a secret from its configured value, eg. `cmd:pass show github/token`.
+    #[inline]
+    #[must_use]
+    pub fn new<S: Into<String>>(reference: S) -> Self {
+        Self {
+            reference: reference.into(),
+        }
+    }
+
+    /// Returns whether the configured value points to the secret rather than being it, in
+    /// which case it can be shown.
+    #[inline]
+    #[must_use]
+    pub fn is_reference(&self) -> bool {
+        is_reference(&self.reference)
+    }
+
+    /// Returns the configured value, which is the secret itself unless `is_reference`.
+    #[inline]
+    #[must_use]
+    pub fn reference(&self) -> &str {
+        &self.reference
+    }
+
+    /// Resolves the secret, reading the variable or file or running the command the first
+    /// time it is used by the process.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the variable is not set, the file cannot be read or may be read by
+    /// other users, the command fails, or the secret is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use alfred_workflow::secret::Secret;
+    ///
+    /// let secret = Secret::new("cmd:echo s3cr3t");
+    /// assert_eq!(secret.expose().unwrap(), "s3cr3t");
+    ///
+    /// let secret = Secret::new("env:SECRET_DOCTEST_UNSET");
+    /// assert_eq!(
+    ///     secret.expose().unwrap_err().to_string(),
+    ///     "secret variable SECRET_DOCTEST_UNSET is not set"
+    /// );
+    ///
+    /// assert_eq!(Secret::new("s3cr3t").expose().unwrap(), "s3cr3t");
+    /// ```
+    pub fn expose(&self) -> Result<String, Error> {
+        let mut resolved = RESOLVED.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(value) = resolved.get(&self.reference) {
+            return Ok(value.clone());
+        }
+        let value = resolve(&self.reference)?;
+        let value = value.trim();
+        if value.is_empty() {
+            return Err(Error::SecretEmpty(self.to_string()));
+        }
+        resolved.insert(self.reference.clone(), value.to_owned());
+        Ok(value.to_owned())
+    }
+}
+
+impl From<&str> for Secret {
+    #[inline]
+    fn from(reference: &str) -> Self {
+        Self::new(reference)
+    }
+}
+
+impl From<String> for Secret {
+    #[inline]
+    fn from(reference: String) -> Self {
+        Self::new(reference)
+    }
+}
+
+/// Shows where the secret comes from, never the secret itself.
+impl fmt::Display for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_reference() {
+            f.write_str(&self.reference)
+        } else {
+            f.write_str("********")
+        }
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret({self})")
+    }
+}
+
+/// Returns whether a configured value points to a secret rather than being one.
+#[must_use]
+pub fn is_reference(value: &str) -> bool {
+    [ENV_PREFIX, FILE_PREFIX, CMD_PREFIX]
+        .iter()
+        .any(|prefix| value.starts_with(prefix))
+}
+
+fn resolve(reference: &str) -> Result<String, Error> {
+    if let Some(name) = reference.strip_prefix(ENV_PREFIX) {
+        let name = name.trim();
+        std::env::var(name).map_err(|_| Error::SecretVariable(name.to_owned()))
+    } else if let Some(path) = reference.strip_prefix(FILE_PREFIX) {
+        read_file(expand_home(path.trim())?)
+    } else if let Some(command) = reference.strip_prefix(CMD_PREFIX) {
+        run(command.trim())
+    } else {
+        Ok(reference.to_owned())
+    }
+}
+
+fn expand_home(path: &str) -> Result<PathBuf, Error> {
+    match path.strip_prefix("~/") {
+        Some(rest) => Ok(dirs::home_dir().ok_or(Error::HomeDirNotFound)?.join(rest)),
+        None => Ok(PathBuf::from(path)),
+    }
+}
+
+fn read_file(path: PathBuf) -> Result<String, Error> {
+    let metadata = match fs::metadata(&path) {
+        Ok(metadata) => metadata,
+        Err(source) => return Err(Error::SecretFile { path, source }),
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = metadata.permissions().mode() & 0o777;
+        if mode & 0o077 != 0 {
+            return Err(Error::SecretFilePermissions { path, mode });
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = metadata;
+    fs::read_to_string(&path).map_err(|source| Error::SecretFile { path, source })
+}
+
+fn run(command: &str) -> Result<String, Error> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| Error::SecretCommand {
+            command: command.to_owned(),
+            reason: e.to_string(),
+        })?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = match stderr.trim() {
+            "" => output.status.to_string(),
+            stderr => format!("{}: {stderr}", output.status),
+        };
+        return Err(Error::SecretCommand {
+            command: command.to_owned(),
+            reason,
+        });
+    }
+    String::from_utf8(output.stdout).map_err(|_| Error::SecretCommand {
+        command: command.to_owned(),
+        reason: "output is not UTF-8".to_owned(),
+    })
+}
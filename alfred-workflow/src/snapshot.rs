@@ -0,0 +1,513 @@
+//! Snapshots of a workflow's cache as JSON or NDJSON, so a cache can be shared instead of
+//! everyone refreshing it from the API.
+//!
+//! A JSON snapshot is a single object:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "workflow": "github",
+//!   "schema_version": 3,
+//!   "exported_at": "2024-03-01T09:00:00Z",
+//!   "refreshed_at": { "repositories": "2024-03-01T08:00:00Z" },
+//!   "tables": { "repositories": [{ "name": "payments-api", "...": "..." }] }
+//! }
+//! ```
+//!
+//! An NDJSON snapshot has the same header, without `tables`, on its first line followed by a
+//! line per row, eg. `{"table": "repositories", "row": { ... }}`.
+//!
+//! Importing merges by default: rows replace those with the same primary key, and rows of
+//! tables without one are added unless already present. Replacing empties the tables first.
+//! Either way the resources are marked as refreshed when the snapshot's were.
+//!
+use crate::errors::Error;
+use crate::{fts, migrations, refresh};
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::types::{Value as SqlValue, ValueRef};
+use rusqlite::{Connection, Transaction};
+use serde_json::{Map, Number, Value};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fmt;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Version of the snapshot format, bumped on incompatible changes.
+pub const VERSION: u64 = 1;
+
+/// How a snapshot is written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    /// A single JSON object.
+    #[default]
+    Json,
+
+    /// A header line followed by a JSON object per row.
+    Ndjson,
+}
+
+impl Format {
+    /// Guesses the format from a file name: NDJSON for `.ndjson` and `.jsonl`, JSON otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use alfred_workflow::snapshot::Format;
+    /// use std::path::Path;
+    ///
+    /// assert_eq!(Format::from_path(Path::new("github.ndjson")), Format::Ndjson);
+    /// assert_eq!(Format::from_path(Path::new("github.json")), Format::Json);
+    /// ```
+    #[must_use]
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(OsStr::to_str) {
+            Some("ndjson" | "jsonl") => Self::Ndjson,
+            _ => Self::Json,
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "ndjson" | "jsonl" => Ok(Self::Ndjson),
+            _ => Err(format!("unknown format '{s}', expected json or ndjson")),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Json => "json",
+            Self::Ndjson => "ndjson",
+        })
+    }
+}
+
+/// How importing a snapshot treats the rows already cached.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ImportMode {
+    /// Adds the snapshot's rows, replacing those with the same primary key.
+    #[default]
+    Merge,
+
+    /// Replaces every row with the snapshot's.
+    Replace,
+}
+
+/// The part of a workflow's cache a snapshot holds.
+#[derive(Debug, Clone, Copy)]
+pub struct Contents<'a> {
+    /// Name of the workflow, whose snapshots are the only ones it imports.
+    pub workflow: &'a str,
+
+    /// The cached tables, tables referring to others after them.
+    pub tables: &'a [&'a str],
+
+    /// The resources tracked in the refreshes table.
+    pub resources: &'a [&'a str],
+
+    /// The resources indexed for full-text search, whose index is rebuilt rather than held.
+    pub index: &'a [fts::Source],
+}
+
+/// Writes a snapshot of the tables, returning the number of rows written.
+///
+/// # Errors
+///
+/// Will return `Err` if the tables cannot be read or the snapshot cannot be written.
+///
+/// # Examples
+///
+/// ```
+/// use alfred_workflow::snapshot::{self, Contents, Format, ImportMode};
+/// use alfred_workflow::{migrations::{self, Migration}, refresh};
+/// use anyhow::Error;
+/// use rusqlite::Connection;
+///
+/// const MIGRATIONS: &[Migration] = &[
+///     Migration::new(1, "create repositories table", "CREATE TABLE repositories (name TEXT NOT NULL PRIMARY KEY, stars INTEGER);"),
+///     Migration::new(2, "create refreshes table", refresh::SCHEMA),
+///     Migration::new(3, "add refresh outcomes", refresh::OUTCOME_SCHEMA),
+/// ];
+/// const CONTENTS: Contents = Contents {
+///     workflow: "github",
+///     tables: &["repositories"],
+///     resources: &["repositories"],
+///     index: &[],
+/// };
+///
+/// fn main() -> Result<(), Error> {
+///     let mut conn = Connection::open_in_memory()?;
+///     migrations::run(&mut conn, MIGRATIONS)?;
+///     conn.execute_batch("INSERT INTO repositories VALUES ('payments', 3), ('website', NULL);")?;
+///
+///     let mut exported = Vec::new();
+///     assert_eq!(snapshot::export(&conn, &CONTENTS, Format::Ndjson, &mut exported)?, 2);
+///
+///     let mut other = Connection::open_in_memory()?;
+///     migrations::run(&mut other, MIGRATIONS)?;
+///     other.execute("INSERT INTO repositories VALUES ('payments', 1)", [])?;
+///     let imported = snapshot::import(&mut other, &CONTENTS, Format::Ndjson, ImportMode::Merge, &exported[..])?;
+///     assert_eq!(imported, 2);
+///
+///     let stars: i64 = other.query_row("SELECT stars FROM repositories WHERE name = 'payments'", [], |row| row.get(0))?;
+///     assert_eq!(stars, 3);
+///     Ok(())
+/// }
+/// ```
+pub fn export<W: Write>(
+    conn: &Connection,
+    contents: &Contents,
+    format: Format,
+    mut writer: W,
+) -> Result<usize, Error> {
+    // read every table as of the same moment, even if a refresh swaps them meanwhile
+    let tx = conn.unchecked_transaction()?;
+    let mut snapshot = header(&tx, contents)?;
+    let mut rows = 0;
+    match format {
+        Format::Json => {
+            let mut tables = Map::new();
+            for table in contents.tables {
+                let table_rows = read_rows(&tx, table)?;
+                rows += table_rows.len();
+                tables.insert(
+                    (*table).to_owned(),
+                    table_rows.into_iter().map(Value::Object).collect(),
+                );
+            }
+            snapshot.insert("tables".to_owned(), Value::Object(tables));
+            serde_json::to_writer_pretty(&mut writer, &snapshot)
+                .map_err(|e| Error::WriteSnapshot(e.into()))?;
+            writeln!(writer).map_err(Error::WriteSnapshot)?;
+        }
+        Format::Ndjson => {
+            write_line(&mut writer, &Value::Object(snapshot))?;
+            for table in contents.tables {
+                for row in read_rows(&tx, table)? {
+                    let mut line = Map::new();
+                    line.insert("table".to_owned(), Value::from(*table));
+                    line.insert("row".to_owned(), Value::Object(row));
+                    write_line(&mut writer, &Value::Object(line))?;
+                    rows += 1;
+                }
+            }
+        }
+    }
+    writer.flush().map_err(Error::WriteSnapshot)?;
+    Ok(rows)
+}
+
+/// Imports a snapshot written by `export` in a single transaction, returning the number of
+/// rows imported. Holds the refresh lock so a refresh cannot run meanwhile.
+///
+/// # Errors
+///
+/// Will return `Err`, importing nothing, if the snapshot is malformed, is of another workflow
+/// or a newer schema, or cannot be written to the database.
+pub fn import<R: BufRead>(
+    conn: &mut Connection,
+    contents: &Contents,
+    format: Format,
+    mode: ImportMode,
+    reader: R,
+) -> Result<usize, Error> {
+    let _lock = refresh::lock(conn)?;
+    let tx = conn.transaction()?;
+    let mut importer = Importer {
+        tx: &tx,
+        contents,
+        mode,
+        columns: HashMap::new(),
+        rows: 0,
+    };
+
+    match format {
+        Format::Json => {
+            let snapshot: Value = serde_json::from_reader(reader)
+                .map_err(|e| Error::InvalidSnapshot(e.to_string()))?;
+            let Value::Object(mut snapshot) = snapshot else {
+                return Err(Error::InvalidSnapshot("not a JSON object".to_owned()));
+            };
+            let tables = snapshot.remove("tables");
+            importer.start(&snapshot)?;
+            let Some(Value::Object(mut tables)) = tables else {
+                return Err(Error::InvalidSnapshot("tables is missing".to_owned()));
+            };
+            // in order, so rows referring to others come after them
+            for table in contents.tables {
+                let rows = match tables.remove(*table) {
+                    Some(Value::Array(rows)) => rows,
+                    Some(_) => {
+                        return Err(Error::InvalidSnapshot(format!("{table} is not a list")));
+                    }
+                    None => continue,
+                };
+                for row in rows {
+                    importer.insert(table, row)?;
+                }
+            }
+            if let Some(table) = tables.keys().next() {
+                return Err(Error::InvalidSnapshot(format!("unknown table {table}")));
+            }
+        }
+        Format::Ndjson => {
+            let mut lines = reader.lines();
+            let header = match lines.next() {
+                Some(line) => parse_line(&line.map_err(Error::ReadSnapshot)?, 1)?,
+                None => return Err(Error::InvalidSnapshot("empty".to_owned())),
+            };
+            importer.start(&header)?;
+            for (n, line) in lines.enumerate() {
+                let line = line.map_err(Error::ReadSnapshot)?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let mut line = parse_line(&line, n + 2)?;
+                let (Some(Value::String(table)), Some(row)) =
+                    (line.remove("table"), line.remove("row"))
+                else {
+                    return Err(Error::InvalidSnapshot(format!(
+                        "line {} has no table and row",
+                        n + 2
+                    )));
+                };
+                importer.insert(&table, row)?;
+            }
+        }
+    }
+
+    let rows = importer.rows;
+    // the index is not part of the snapshot, so is rebuilt from what the cache now holds
+    fts::rebuild(&tx, contents.index)?;
+    tx.commit()?;
+    Ok(rows)
+}
+
+struct Importer<'a> {
+    tx: &'a Transaction<'a>,
+    contents: &'a Contents<'a>,
+    mode: ImportMode,
+    /// The columns of each table imported into, and whether it has a primary key.
+    columns: HashMap<String, (Vec<String>, bool)>,
+    rows: usize,
+}
+
+impl Importer<'_> {
+    /// Checks the header, then empties the tables if replacing them and restores when the
+    /// resources were refreshed.
+    fn start(&mut self, header: &Map<String, Value>) -> Result<(), Error> {
+        let version = header.get("version").and_then(Value::as_u64);
+        if version != Some(VERSION) {
+            return Err(Error::SnapshotVersion {
+                found: version.unwrap_or_default(),
+                supported: VERSION,
+            });
+        }
+        let workflow = header.get("workflow").and_then(Value::as_str);
+        if workflow != Some(self.contents.workflow) {
+            return Err(Error::SnapshotWorkflow {
+                expected: self.contents.workflow.to_owned(),
+                found: workflow.unwrap_or_default().to_owned(),
+            });
+        }
+        let schema_version = header
+            .get("schema_version")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| Error::InvalidSnapshot("schema_version is missing".to_owned()))?;
+        let supported = migrations::schema_version(self.tx)?;
+        if schema_version > u64::from(supported) {
+            return Err(Error::UnsupportedSchemaVersion {
+                found: u32::try_from(schema_version).unwrap_or(u32::MAX),
+                supported,
+            });
+        }
+
+        if self.mode == ImportMode::Replace {
+            for table in self.contents.tables.iter().rev() {
+                self.tx
+                    .execute(&format!("DELETE FROM main.\"{table}\""), [])?;
+            }
+        }
+
+        let existing = refresh::status(self.tx, self.contents.resources)?;
+        let refreshed_at = header.get("refreshed_at").and_then(Value::as_object);
+        for status in existing {
+            let Some(at) = refreshed_at
+                .and_then(|r| r.get(&status.resource))
+                .and_then(Value::as_str)
+                .and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+            else {
+                continue;
+            };
+            let at = at.with_timezone(&Utc);
+            let newer = status.refreshed_at.is_none_or(|r| r < at);
+            if self.mode == ImportMode::Replace || newer {
+                refresh::restore(self.tx, &status.resource, at)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn insert(&mut self, table: &str, row: Value) -> Result<(), Error> {
+        if !self.contents.tables.contains(&table) {
+            return Err(Error::InvalidSnapshot(format!("unknown table {table}")));
+        }
+        let Value::Object(row) = row else {
+            return Err(Error::InvalidSnapshot(format!(
+                "a row of {table} is not an object"
+            )));
+        };
+        if !self.columns.contains_key(table) {
+            let columns = table_columns(self.tx, table)?;
+            self.columns.insert(table.to_owned(), columns);
+        }
+        let (columns, primary_key) = &self.columns[table];
+
+        let mut names = Vec::with_capacity(row.len());
+        let mut values = Vec::with_capacity(row.len());
+        for (column, value) in row {
+            if !columns.contains(&column) {
+                return Err(Error::InvalidSnapshot(format!(
+                    "unknown column {table}.{column}"
+                )));
+            }
+            names.push(format!("\"{column}\""));
+            values.push(sql_value(value).ok_or_else(|| {
+                Error::InvalidSnapshot(format!("{table}.{column} is not a plain value"))
+            })?);
+        }
+        let placeholders = (1..=names.len())
+            .map(|i| format!("?{i}"))
+            .collect::<Vec<_>>();
+        let sql = if *primary_key || self.mode == ImportMode::Replace {
+            format!(
+                "INSERT OR REPLACE INTO main.\"{table}\" ({}) VALUES ({})",
+                names.join(", "),
+                placeholders.join(", ")
+            )
+        } else {
+            // without a key to replace by, skip rows that are already cached
+            let matches = names
+                .iter()
+                .zip(&placeholders)
+                .map(|(name, p)| format!("{name} IS {p}"))
+                .collect::<Vec<_>>();
+            format!(
+                "INSERT INTO main.\"{table}\" ({}) SELECT {} WHERE NOT EXISTS (SELECT 1 FROM main.\"{table}\" WHERE {})",
+                names.join(", "),
+                placeholders.join(", "),
+                matches.join(" AND ")
+            )
+        };
+        self.tx
+            .prepare_cached(&sql)?
+            .execute(rusqlite::params_from_iter(values))?;
+        self.rows += 1;
+        Ok(())
+    }
+}
+
+fn header(conn: &Connection, contents: &Contents) -> Result<Map<String, Value>, Error> {
+    let refreshed_at = refresh::status(conn, contents.resources)?
+        .into_iter()
+        .filter_map(|s| Some((s.resource, Value::from(timestamp(s.refreshed_at?)))))
+        .collect::<Map<_, _>>();
+    let mut header = Map::new();
+    header.insert("version".to_owned(), Value::from(VERSION));
+    header.insert("workflow".to_owned(), Value::from(contents.workflow));
+    header.insert(
+        "schema_version".to_owned(),
+        Value::from(migrations::schema_version(conn)?),
+    );
+    header.insert("exported_at".to_owned(), Value::from(timestamp(Utc::now())));
+    header.insert("refreshed_at".to_owned(), Value::Object(refreshed_at));
+    Ok(header)
+}
+
+fn read_rows(conn: &Connection, table: &str) -> Result<Vec<Map<String, Value>>, Error> {
+    let mut stmt = conn.prepare(&format!("SELECT * FROM main.\"{table}\""))?;
+    let names = stmt
+        .column_names()
+        .into_iter()
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+    stmt.query_map([], |row| {
+        names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| Ok((name.clone(), json_value(row.get_ref(i)?))))
+            .collect()
+    })?
+    .map(|r| Ok(r?))
+    .collect()
+}
+
+/// Returns the table's columns and whether it has a primary key.
+fn table_columns(conn: &Connection, table: &str) -> Result<(Vec<String>, bool), Error> {
+    let mut primary_key = false;
+    let columns = conn
+        .prepare(&format!("PRAGMA main.table_info(\"{table}\")"))?
+        .query_map([], |row| {
+            Ok((row.get::<_, String>("name")?, row.get::<_, i64>("pk")?))
+        })?
+        .map(|column| {
+            let (name, pk) = column?;
+            primary_key |= pk > 0;
+            Ok(name)
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    if columns.is_empty() {
+        return Err(Error::TableNotFound(table.to_owned()));
+    }
+    Ok((columns, primary_key))
+}
+
+fn json_value(value: ValueRef<'_>) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(i) => Value::from(i),
+        ValueRef::Real(f) => Number::from_f64(f).map_or(Value::Null, Value::Number),
+        ValueRef::Text(s) | ValueRef::Blob(s) => Value::from(String::from_utf8_lossy(s)),
+    }
+}
+
+fn sql_value(value: Value) -> Option<SqlValue> {
+    Some(match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(b) => SqlValue::Integer(i64::from(b)),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => SqlValue::Integer(i),
+            None => SqlValue::Real(n.as_f64()?),
+        },
+        Value::String(s) => SqlValue::Text(s),
+        Value::Array(_) | Value::Object(_) => return None,
+    })
+}
+
+fn parse_line(line: &str, n: usize) -> Result<Map<String, Value>, Error> {
+    match serde_json::from_str(line) {
+        Ok(Value::Object(object)) => Ok(object),
+        Ok(_) => Err(Error::InvalidSnapshot(format!(
+            "line {n} is not a JSON object"
+        ))),
+        Err(e) => Err(Error::InvalidSnapshot(format!("line {n}: {e}"))),
+    }
+}
+
+fn write_line<W: Write>(writer: &mut W, value: &Value) -> Result<(), Error> {
+    serde_json::to_writer(&mut *writer, value).map_err(|e| Error::WriteSnapshot(e.into()))?;
+    writeln!(writer).map_err(Error::WriteSnapshot)
+}
+
+#[inline]
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
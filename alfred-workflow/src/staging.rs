@@ -0,0 +1,145 @@
+//! Builds a refresh into staging tables and swaps them in atomically once it succeeds, so
+//! queries never see a partially refreshed cache and a failed refresh leaves the old one intact.
+//!
+//! Staging tables are temporary tables named `<table>_staging`, private to the refreshing
+//! connection and dropped with it should the refresh die.
+//!
+use crate::changes::{self, Summary, Tracked};
+use crate::errors::Error;
+use crate::fts;
+use chrono::Utc;
+use log::info;
+use rusqlite::Connection;
+
+/// Returns the name of the staging table for `table`.
+#[inline]
+#[must_use]
+pub fn table(table: &str) -> String {
+    format!("{table}_staging")
+}
+
+/// Creates an empty staging table, with the same column types, `NOT NULL` and primary key
+/// constraints, for each of the tables.
+///
+/// # Errors
+///
+/// Will return `Err` if a table does not exist or the staging table cannot be created.
+pub fn create(conn: &Connection, tables: &[&str]) -> Result<(), Error> {
+    for name in tables {
+        let staging = table(name);
+        conn.execute_batch(&format!(
+            "DROP TABLE IF EXISTS temp.{staging};
+             CREATE TEMP TABLE {staging} ({});",
+            columns(conn, name)?
+        ))?;
+    }
+    Ok(())
+}
+
+/// Returns the column definitions of the table, without foreign keys which could not refer to
+/// tables in another schema.
+///
+/// Read from the schema rather than using `CREATE TABLE .. AS SELECT`, which would lose the
+/// declared types and with them how values such as dates are stored.
+fn columns(conn: &Connection, table: &str) -> Result<String, Error> {
+    let mut primary_key = Vec::new();
+    let mut columns = conn
+        .prepare(&format!("PRAGMA main.table_info({table})"))?
+        .query_map([], |row| {
+            let name: String = row.get("name")?;
+            let kind: String = row.get("type")?;
+            let not_null: bool = row.get("notnull")?;
+            let pk: i64 = row.get("pk")?;
+            Ok((name, kind, not_null, pk))
+        })?
+        .map(|column| {
+            let (name, kind, not_null, pk) = column?;
+            if pk > 0 {
+                primary_key.push((pk, format!("\"{name}\"")));
+            }
+            let not_null = if not_null { " NOT NULL" } else { "" };
+            Ok(format!("\"{name}\" {kind}{not_null}"))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    if columns.is_empty() {
+        return Err(Error::TableNotFound(table.to_owned()));
+    }
+    if !primary_key.is_empty() {
+        primary_key.sort();
+        let keys = primary_key.into_iter().map(|(_, name)| name);
+        columns.push(format!(
+            "PRIMARY KEY ({})",
+            keys.collect::<Vec<_>>().join(", ")
+        ));
+    }
+    Ok(columns.join(", "))
+}
+
+/// Replaces the contents of each table with its staging table and rebuilds the search index of
+/// the `index` resources from them in a single transaction, then drops the staging tables.
+/// Returns what changed in the `tracked` tables, recording when their rows were first seen.
+///
+/// # Errors
+///
+/// Will return `Err` if any table cannot be replaced, in which case none are.
+///
+/// # Examples
+///
+/// ```
+/// use alfred_workflow::staging;
+/// use anyhow::Error;
+/// use rusqlite::Connection;
+///
+/// fn main() -> Result<(), Error> {
+///     let mut conn = Connection::open_in_memory()?;
+///     conn.execute_batch(
+///         "CREATE TABLE repositories (name TEXT NOT NULL PRIMARY KEY);
+///          INSERT INTO repositories (name) VALUES ('old');",
+///     )?;
+///
+///     staging::create(&conn, &["repositories"])?;
+///     conn.execute("INSERT INTO repositories_staging (name) VALUES ('new')", [])?;
+///     let name: String = conn.query_row("SELECT name FROM repositories", [], |row| row.get(0))?;
+///     assert_eq!(name, "old");
+///
+///     let summary = staging::swap(&mut conn, &["repositories"], &[], &[])?;
+///     let name: String = conn.query_row("SELECT name FROM repositories", [], |row| row.get(0))?;
+///     assert_eq!(name, "new");
+///     assert_eq!(summary.to_string(), "no changes");
+///     Ok(())
+/// }
+/// ```
+pub fn swap(
+    conn: &mut Connection,
+    tables: &[&str],
+    index: &[fts::Source],
+    tracked: &[Tracked],
+) -> Result<Summary, Error> {
+    let tx = conn.transaction()?;
+    let summary = changes::track(&tx, tracked, Utc::now().timestamp())?;
+    for name in tables {
+        let staging = table(name);
+        tx.execute(&format!("DELETE FROM main.{name}"), [])?;
+        let rows = tx.execute(
+            &format!("INSERT INTO main.{name} SELECT * FROM temp.{staging}"),
+            [],
+        )?;
+        info!("swapped in {rows} rows of {name}");
+    }
+    fts::rebuild(&tx, index)?;
+    tx.commit()?;
+    discard(conn, tables)?;
+    Ok(summary)
+}
+
+/// Drops the staging tables, leaving the tables untouched.
+///
+/// # Errors
+///
+/// Will return `Err` if a staging table cannot be dropped.
+pub fn discard(conn: &Connection, tables: &[&str]) -> Result<(), Error> {
+    for name in tables {
+        conn.execute_batch(&format!("DROP TABLE IF EXISTS temp.{};", table(name)))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,278 @@
+//! The interface of a workflow that caches an API in `SQLite` and searches the cache, which
+//! `runner::run` turns into the standard command line.
+//!
+//! A workflow provides its API client and storage: fetching into staging tables and querying
+//! the cache. Refreshing with a lock and staging tables, background refreshes, ranking opened
+//! results, the cache status and what each refresh changed are provided.
+//!
+use crate::changes::{self, Summary, Tracked};
+use crate::config::WorkflowConfig;
+use crate::errors::Error;
+use crate::failure::ToFailure;
+use crate::output::Record;
+use crate::search::SearchQuery;
+use crate::snapshot::{self, Contents, Format, ImportMode};
+use crate::{frecency, fts, refresh, staging};
+use chrono::{DateTime, Utc};
+use log::{error, info, warn};
+use rusqlite::Connection;
+use serde::Serialize;
+use std::io::{BufRead, Write};
+use std::time::{Duration, Instant};
+
+/// A named kind of search, the first word of the query, eg. `m` for Datadog monitors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Mode {
+    pub name: &'static str,
+    pub about: &'static str,
+}
+
+impl Mode {
+    #[inline]
+    #[must_use]
+    pub const fn new(name: &'static str, about: &'static str) -> Self {
+        Self { name, about }
+    }
+}
+
+/// A workflow caching an API.
+pub trait Workflow: Sized {
+    /// The workflow's configuration.
+    type Config: WorkflowConfig;
+
+    /// The workflow's error, which can tell which known failure it is.
+    type Error: From<Error> + ToFailure + std::error::Error + Send + Sync + 'static;
+
+    /// Name of the service, eg. `GitHub`.
+    const SERVICE: &'static str;
+
+    /// The cached resources, as tracked in the refreshes table.
+    const RESOURCES: &'static [&'static str];
+
+    /// The tables built in staging tables while refreshing, the resources by default. Tables
+    /// referring to others must come after them.
+    const STAGED_TABLES: &'static [&'static str] = Self::RESOURCES;
+
+    /// The resources indexed for full-text search, rebuilt whenever the cache is replaced.
+    const SEARCH_INDEX: &'static [fts::Source] = &[];
+
+    /// The resources whose changes are tracked, to summarize each refresh and list what
+    /// appeared recently.
+    const TRACKED: &'static [Tracked] = &[];
+
+    /// The kinds of search, if more than one.
+    const MODES: &'static [Mode] = &[];
+
+    /// Returns the hosts the workflow's results link to, which are the only ones it will open,
+    /// eg. the sites of each profile's API.
+    fn allowed_hosts(config: &Self::Config) -> Vec<String>;
+
+    /// Returns the configured command opening results, if not the platform's default.
+    fn open_command(config: &Self::Config) -> Option<&str>;
+
+    /// Returns how old the cache may be before it is refreshed in the background.
+    fn cache_ttl(config: &Self::Config) -> Duration;
+
+    /// Returns how old the cache may be before searches suggest refreshing it, zero to never
+    /// suggest it.
+    fn stale_after(config: &Self::Config) -> Duration;
+
+    /// Creates the workflow, opening its cache.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the cache cannot be opened.
+    fn from_config(config: Self::Config) -> Result<Self, Self::Error>;
+
+    /// The connection to the cache.
+    fn connection(&self) -> &Connection;
+
+    /// The connection to the cache, to write to it.
+    fn connection_mut(&mut self) -> &mut Connection;
+
+    /// Fetches every resource from the API into the staging tables, reporting each page
+    /// fetched with `refresh::report`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the API returns an error or writing to the database fails.
+    fn fetch(&mut self) -> Result<(), Self::Error>;
+
+    /// Searches the cache, returning the rows found along with the items showing them.
+    ///
+    /// # Arguments
+    /// * `mode` - the name of one of the `MODES`, if the query started with one.
+    /// * `query` - the rest of the query.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if querying the database fails.
+    fn query(&mut self, mode: Option<&str>, query: &str) -> Result<Vec<Record>, Self::Error>;
+
+    /// Refreshes the cache, unless another refresh is already running, returning what changed.
+    /// The old cache is kept unless every resource was fetched.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if fetching fails or a refresh is in progress.
+    fn refresh(&mut self) -> Result<Summary, Self::Error> {
+        let _lock = refresh::lock(self.connection())?;
+        let start = Instant::now();
+        info!("{} refresh started", Self::SERVICE);
+        refresh::start(self.connection(), Self::RESOURCES)?;
+        staging::create(self.connection(), Self::STAGED_TABLES)?;
+        let result = self.fetch().and_then(|()| {
+            Ok(staging::swap(
+                self.connection_mut(),
+                Self::STAGED_TABLES,
+                Self::SEARCH_INDEX,
+                Self::TRACKED,
+            )?)
+        });
+        if let Err(e) = &result {
+            error!(
+                "{} refresh failed after {:?}: {e}",
+                Self::SERVICE,
+                start.elapsed()
+            );
+            // best-effort, the refresh's own error is the one to report
+            if let Err(cleanup) = staging::discard(self.connection(), Self::STAGED_TABLES) {
+                warn!("failed to discard the staging tables: {cleanup}");
+            }
+            if let Err(cleanup) = refresh::fail(
+                self.connection(),
+                Self::RESOURCES,
+                start.elapsed(),
+                &e.to_string(),
+            ) {
+                warn!("failed to record the failed refresh: {cleanup}");
+            }
+        }
+        let summary = result?;
+        refresh::succeed(self.connection(), Self::RESOURCES, start.elapsed())?;
+        info!(
+            "{} refresh finished in {:?}: {summary}",
+            Self::SERVICE,
+            start.elapsed()
+        );
+        Ok(summary)
+    }
+
+    /// Starts refreshing the cache in a detached process if it was last refreshed longer than
+    /// `ttl` ago, returning whether a refresh is running.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if reading the database fails or the refresh cannot be started.
+    fn refresh_if_stale(&self, ttl: Duration, args: &[&str]) -> Result<bool, Self::Error> {
+        Ok(refresh::refresh_if_stale(
+            self.connection(),
+            Self::RESOURCES,
+            ttl,
+            args,
+        )?)
+    }
+
+    /// Records the result was opened using the given query, so it ranks higher next time.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if writing to the database fails.
+    fn record_open(&self, url: &str, query: &str) -> Result<(), Self::Error> {
+        Ok(frecency::record(self.connection(), url, query)?)
+    }
+
+    /// Returns when each resource was last refreshed, how many rows it holds and how its last
+    /// refresh went.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if reading the database fails.
+    fn status(&self) -> Result<Vec<refresh::Status>, Self::Error> {
+        Ok(refresh::status(self.connection(), Self::RESOURCES)?)
+    }
+
+    /// Returns the rows first seen since `since` matching the query, newest first.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if reading the database fails.
+    fn first_seen_since(
+        &self,
+        since: DateTime<Utc>,
+        query: &SearchQuery,
+    ) -> Result<Vec<changes::NewRow>, Self::Error> {
+        Ok(changes::first_seen_since(
+            self.connection(),
+            Self::TRACKED,
+            since,
+            query,
+        )?)
+    }
+
+    /// Returns how far the running refresh got, `None` if none is running.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if reading the database fails.
+    fn progress(&self) -> Result<Option<refresh::Progress>, Self::Error> {
+        Ok(refresh::progress(self.connection(), Self::RESOURCES)?)
+    }
+
+    /// Returns when the least recently refreshed resource was refreshed, `None` if one never
+    /// was.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if reading the database fails.
+    fn last_refreshed(&self) -> Result<Option<DateTime<Utc>>, Self::Error> {
+        Ok(refresh::last_refreshed(self.connection(), Self::RESOURCES)?)
+    }
+
+    /// Writes a snapshot of the cache, returning the number of rows written.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the cache cannot be read or the snapshot cannot be written.
+    fn export<W: Write>(&self, format: Format, writer: W) -> Result<usize, Self::Error> {
+        Ok(snapshot::export(
+            self.connection(),
+            &Self::snapshot_contents(),
+            format,
+            writer,
+        )?)
+    }
+
+    /// Imports a snapshot written by `export`, returning the number of rows imported.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err`, importing nothing, if the snapshot is malformed or not this
+    /// workflow's, or a refresh is in progress.
+    fn import<R: BufRead>(
+        &mut self,
+        format: Format,
+        mode: ImportMode,
+        reader: R,
+    ) -> Result<usize, Self::Error> {
+        let rows = snapshot::import(
+            self.connection_mut(),
+            &Self::snapshot_contents(),
+            format,
+            mode,
+            reader,
+        )?;
+        info!("imported {rows} rows into the {} cache", Self::SERVICE);
+        Ok(rows)
+    }
+
+    /// The part of the cache snapshots hold.
+    fn snapshot_contents() -> Contents<'static> {
+        Contents {
+            workflow: Self::Config::NAME,
+            tables: Self::STAGED_TABLES,
+            resources: Self::RESOURCES,
+            index: Self::SEARCH_INDEX,
+        }
+    }
+}
@@ -1,11 +1,31 @@
+use alfred_workflow::failure::{Failure, ToFailure};
+use reqwest::StatusCode;
 use thiserror::Error;
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
 #[derive(Debug, Error)]
 pub enum Error {
-    #[error("HTTP error: {}", _0)]
-    Http(String),
+    #[error("HTTP error {}: {}", _0, _1)]
+    Http(StatusCode, String),
 
     #[error(transparent)]
-    ReqwestError(#[from] reqwest::Error),
+    Request(#[from] reqwest::Error),
+
+    #[error(transparent)]
+    AlfredWorkflow(#[from] alfred_workflow::errors::Error),
+}
+
+impl ToFailure for Error {
+    fn to_failure(&self) -> Failure {
+        match self {
+            Self::Http(status @ (StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN), _) => {
+                Failure::Unauthorized {
+                    service: "Buildkite",
+                    status: status.as_u16(),
+                }
+            }
+            Self::AlfredWorkflow(e) => e.to_failure(),
+            _ => Failure::Other(self.to_string()),
+        }
+    }
 }
@@ -2,74 +2,106 @@ pub mod errors;
 pub mod models;
 
 use crate::buildkite_api::models::{Organization, Pipeline};
+use alfred_workflow::http::Client;
 use errors::{Error, Result};
+use log::debug;
 use regex::Regex;
-use reqwest::header::{HeaderValue, CONTENT_TYPE, LINK};
+use reqwest::header::{CONTENT_TYPE, HeaderValue, LINK};
 
 #[derive(Debug)]
 pub struct BuildkiteAPI<'a> {
+    client: &'a Client,
+    url: &'a str,
     token: &'a str,
     re: Regex,
 }
 
 impl<'a> BuildkiteAPI<'a> {
     #[inline]
-    pub fn new(token: &'a str) -> Self {
+    pub fn new(client: &'a Client, url: &'a str, token: &'a str) -> Self {
         let re = Regex::new(r#"<(.*)>; rel="next""#).unwrap();
-        Self { token, re }
+        Self {
+            client,
+            url,
+            token,
+            re,
+        }
     }
 
     #[inline]
-    pub fn get_organizations_paginated(&self) -> OrganizationsIter {
+    pub fn get_organizations_paginated(&self) -> OrganizationsIter<'_> {
         OrganizationsIter {
             api: self,
-            next: Some("https://api.buildkite.com/v2/organizations?per_page=100".to_owned()),
+            next: Some(format!("{}/organizations?per_page=100", self.url)),
         }
     }
 
     #[inline]
-    pub fn get_pipelines_paginated(&self, organization: &str) -> PipelinesIter {
+    pub fn get_pipelines_paginated(&self, organization: &str) -> PipelinesIter<'_> {
         PipelinesIter {
             api: self,
             next: Some(format!(
-                "https://api.buildkite.com/v2/organizations/{organization}/pipelines?per_page=100"
+                "{}/organizations/{organization}/pipelines?per_page=100",
+                self.url
             )),
         }
     }
 
     #[inline]
     fn fetch_organizations(&self, url: &str) -> Result<OrganizationResponse> {
-        let response = reqwest::blocking::Client::new()
+        let request = self
+            .client
             .get(url)
             .bearer_auth(self.token)
-            .header(CONTENT_TYPE, "application/json")
-            .send()?;
+            .header(CONTENT_TYPE, "application/json");
+        let response = self.client.send(request)?;
 
-        if !response.status().is_success() {
-            return Err(Error::Http(response.text()?));
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Error::Http(status, response.text()?));
         }
 
         let link = response.headers().get(LINK);
         let next = self.extract_next(link);
         let results: Vec<Organization> = response.json()?;
+        debug!(
+            "fetched {} organizations, {}",
+            results.len(),
+            if next.is_some() {
+                "more to come"
+            } else {
+                "last page"
+            }
+        );
         Ok(OrganizationResponse { next, results })
     }
 
     #[inline]
     fn fetch_pipelines(&self, url: &str) -> Result<PipelineResponse> {
-        let response = reqwest::blocking::Client::new()
+        let request = self
+            .client
             .get(url)
             .bearer_auth(self.token)
-            .header(CONTENT_TYPE, "application/json")
-            .send()?;
+            .header(CONTENT_TYPE, "application/json");
+        let response = self.client.send(request)?;
 
-        if !response.status().is_success() {
-            return Err(Error::Http(response.text()?));
+        let status = response.status();
+        if !status.is_success() {
+            return Err(Error::Http(status, response.text()?));
         }
 
         let link = response.headers().get(LINK);
         let next = self.extract_next(link);
         let results: Vec<Pipeline> = response.json()?;
+        debug!(
+            "fetched {} pipelines, {}",
+            results.len(),
+            if next.is_some() {
+                "more to come"
+            } else {
+                "last page"
+            }
+        );
         Ok(PipelineResponse { next, results })
     }
 
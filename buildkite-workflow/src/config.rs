@@ -0,0 +1,23 @@
+use alfred_workflow::config::{ApiConfig, CacheConfig, Loader, WorkflowConfig};
+
+/// The REST API, version 2.
+const DEFAULT_API_URL: &str = "https://api.buildkite.com/v2";
+
+/// The workflow's configuration, see `alfred_workflow::config` for where it is read from.
+#[derive(Debug)]
+pub struct Config {
+    pub profiles: Vec<ApiConfig>,
+    pub cache: CacheConfig,
+}
+
+impl WorkflowConfig for Config {
+    const NAME: &'static str = "buildkite";
+
+    fn read(loader: &mut Loader) -> Self {
+        Self {
+            profiles: loader
+                .profiles(|loader, name| ApiConfig::read(loader, name, DEFAULT_API_URL)),
+            cache: CacheConfig::read(loader),
+        }
+    }
+}
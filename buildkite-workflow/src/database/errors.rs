@@ -1,3 +1,4 @@
+use alfred_workflow::failure::{Failure, ToFailure};
 use thiserror::Error;
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -6,4 +7,16 @@ pub type Result<T, E = Error> = std::result::Result<T, E>;
 pub enum Error {
     #[error(transparent)]
     Sqlite(#[from] rusqlite::Error),
+
+    #[error(transparent)]
+    AlfredWorkflow(#[from] alfred_workflow::errors::Error),
+}
+
+impl ToFailure for Error {
+    fn to_failure(&self) -> Failure {
+        match self {
+            Self::AlfredWorkflow(e) => e.to_failure(),
+            Self::Sqlite(_) => Failure::Other(self.to_string()),
+        }
+    }
 }
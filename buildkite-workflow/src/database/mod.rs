@@ -2,8 +2,79 @@ pub mod errors;
 pub mod models;
 
 use crate::database::models::Pipeline;
+use alfred_workflow::migrations::Migration;
+use alfred_workflow::search::{SearchFields, SearchQuery};
+use alfred_workflow::{changes, failure, frecency, fts, refresh};
 use errors::Result;
+use log::debug;
 use rusqlite::{Connection, ToSql};
+use std::collections::HashMap;
+
+const MIGRATIONS: &[Migration] = &[
+    Migration::new(
+        1,
+        "create pipelines table",
+        "CREATE TABLE IF NOT EXISTS pipelines (
+            unique_name TEXT    NOT NULL PRIMARY KEY,
+            name        TEXT    NOT NULL,
+            url         TEXT    NOT NULL
+        );",
+    ),
+    Migration::new(2, "create selections table", frecency::SCHEMA),
+    Migration::new(3, "create refreshes table", refresh::SCHEMA),
+    Migration::new(4, "add refresh duration and error", refresh::OUTCOME_SCHEMA),
+    Migration::new(5, "create search index", fts::SCHEMA),
+    Migration::new(
+        6,
+        "key pipelines by profile",
+        "CREATE TABLE pipelines_profiles (
+            profile     TEXT    NOT NULL DEFAULT '',
+            unique_name TEXT    NOT NULL,
+            name        TEXT    NOT NULL,
+            url         TEXT    NOT NULL,
+            first_seen  INTEGER,
+            PRIMARY KEY (profile, unique_name)
+        );
+        INSERT INTO pipelines_profiles (unique_name, name, url)
+        SELECT unique_name, name, url FROM pipelines;
+        DROP TABLE pipelines;
+        ALTER TABLE pipelines_profiles RENAME TO pipelines;
+        INSERT INTO search_entries (resource, key, url, title, description, tags)
+        SELECT 'pipelines', rowid, url, fts_words(unique_name) AS title, '', ''
+        FROM pipelines
+        ORDER BY length(title), title;
+        INSERT INTO search_index (search_index) VALUES ('rebuild');",
+    ),
+    Migration::new(7, "create refresh progress table", refresh::PROGRESS_SCHEMA),
+];
+
+/// The cached resources, as tracked in the refreshes table and staged while refreshing.
+pub const RESOURCES: &[&str] = &["pipelines"];
+
+/// The resources whose changes each refresh summarizes.
+pub const TRACKED: &[changes::Tracked] = &[changes::Tracked {
+    resource: "pipelines",
+    noun: "pipeline",
+    title: "name",
+    profile: Some("profile"),
+}];
+
+/// The resources indexed for full-text search, keyed by rowid as a pipeline may be cached for
+/// several profiles.
+pub const SEARCH_INDEX: &[fts::Source] = &[fts::Source {
+    resource: "pipelines",
+    select: "SELECT rowid AS key, url, unique_name AS title, '' AS description, '' AS tags
+        FROM pipelines",
+}];
+
+const SEARCH_FIELDS: SearchFields = SearchFields {
+    default: &["name"],
+    qualifiers: &[
+        ("pipeline", "name"),
+        ("org", "substr(unique_name, 1, instr(unique_name, '/') - 1)"),
+    ],
+    profile: Some("profile"),
+};
 
 pub struct DbContext {
     conn: Connection,
@@ -12,65 +83,60 @@ pub struct DbContext {
 impl DbContext {
     #[inline]
     pub fn new(database_url: &str) -> Result<Self> {
-        let conn = Connection::open(database_url)?;
+        let conn = alfred_workflow::open_database_at(database_url, MIGRATIONS)?;
         Ok(DbContext { conn })
     }
 
     #[inline]
-    pub fn run_migrations(&self) -> Result<()> {
-        self.conn.execute_batch(
-            "CREATE TABLE IF NOT EXISTS pipelines (
-                unique_name TEXT    NOT NULL PRIMARY KEY,
-                name        TEXT    NOT NULL,
-                url         TEXT    NOT NULL
-            );",
-        )?;
-        Ok(())
-    }
-
-    #[inline]
-    pub fn delete_pipelines(&self) -> Result<()> {
-        self.conn.execute("DELETE FROM pipelines;", [])?;
-        Ok(())
-    }
-
-    #[inline]
-    pub fn find_pipelines(&self, repo_name: &[String], limit: i64) -> Result<Vec<Pipeline>> {
-        // This will allow searching by full name or just the words within the name;
-        // it's not a regex but it's good enough.
-        let query = format!(
-            "%{}%",
-            repo_name
-                .iter()
-                .flat_map(|s| s.split_terminator(' '))
-                .flat_map(|s| s.split_terminator('_'))
-                .flat_map(|s| s.split_terminator('-'))
-                .collect::<Vec<&str>>()
-                .join("%")
+    pub fn find_pipelines(&self, query: &SearchQuery) -> Result<Vec<Pipeline>> {
+        let search = fts::compile(
+            query,
+            &["pipelines"],
+            "fts.key = pipelines.rowid",
+            &SEARCH_FIELDS,
         );
+        let params = search
+            .params
+            .iter()
+            .map(|p| p as &dyn ToSql)
+            .collect::<Vec<_>>();
 
-        let results = self.conn.prepare(
-            "SELECT unique_name, name, url FROM pipelines WHERE name LIKE ? ORDER BY name ASC LIMIT ?",
-        )?.query_map([&query as &dyn ToSql,&limit], |row| {
-            Ok(Pipeline{
-                unique_name: row.get(0)?,
-                name:row.get(1)?,
-                url:row.get(2)?,
-            })
-        })?
+        let results = self
+            .conn
+            .prepare(&format!(
+                "SELECT profile, unique_name, name, url FROM pipelines {} WHERE {} ORDER BY {} LIMIT {}",
+                search.join,
+                search.clause,
+                search.order_by("name ASC"),
+                fts::CANDIDATES
+            ))?
+            .query_map(&*params, |row| {
+                Ok(Pipeline {
+                    profile: row.get(0)?,
+                    unique_name: row.get(1)?,
+                    name: row.get(2)?,
+                    url: row.get(3)?,
+                })
+            })?
             .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        if results.is_empty() {
+            failure::ensure_cached(&self.conn, &["pipelines"])?;
+        }
         Ok(results)
     }
 
     #[inline]
     pub fn insert_pipelines(&mut self, pipelines: &[Pipeline]) -> Result<()> {
         let tx = self.conn.transaction()?;
-        let mut stmt =
-            tx.prepare("INSERT INTO pipelines (unique_name, name, url) VALUES (?1, ?2, ?3)")?;
+        let mut stmt = tx.prepare(
+            "INSERT INTO pipelines_staging (profile, unique_name, name, url) VALUES (?1, ?2, ?3, ?4)",
+        )?;
 
         for pipeline in pipelines {
             stmt.execute([
-                &pipeline.unique_name as &dyn ToSql,
+                &pipeline.profile as &dyn ToSql,
+                &pipeline.unique_name,
                 &pipeline.name,
                 &pipeline.url,
             ])?;
@@ -78,13 +144,22 @@ impl DbContext {
 
         stmt.finalize()?;
         tx.commit()?;
+        debug!("staged {} pipelines", pipelines.len());
         Ok(())
     }
 
     #[inline]
-    pub fn optimize(&self) -> Result<()> {
-        // since this workflow is READ heavy, let's optimize the SQLite indexes and DB
-        self.conn.execute("VACUUM;", [])?;
-        Ok(())
+    pub fn selection_scores(&self, query: &str) -> Result<HashMap<String, i64>> {
+        Ok(frecency::scores(&self.conn, query)?)
+    }
+
+    #[inline]
+    pub const fn conn(&self) -> &Connection {
+        &self.conn
+    }
+
+    #[inline]
+    pub const fn conn_mut(&mut self) -> &mut Connection {
+        &mut self.conn
     }
 }
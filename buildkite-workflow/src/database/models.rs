@@ -1,5 +1,8 @@
-#[derive(Debug)]
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
 pub struct Pipeline {
+    pub profile: String,
     pub unique_name: String,
     pub name: String,
     pub url: String,
@@ -1,3 +1,4 @@
+use alfred_workflow::failure::{Failure, ToFailure};
 use std::io;
 use thiserror::Error;
 
@@ -11,4 +12,18 @@ pub enum Error {
 
     #[error("failed to write alfred items->json {}", _0)]
     WriteItems(#[from] io::Error),
+
+    #[error(transparent)]
+    AlfredWorkflow(#[from] alfred_workflow::errors::Error),
+}
+
+impl ToFailure for Error {
+    fn to_failure(&self) -> Failure {
+        match self {
+            Self::API(e) => e.to_failure(),
+            Self::SQLite(e) => e.to_failure(),
+            Self::WriteItems(_) => Failure::Other(self.to_string()),
+            Self::AlfredWorkflow(e) => e.to_failure(),
+        }
+    }
 }
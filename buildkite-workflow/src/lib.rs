@@ -2,6 +2,7 @@
 extern crate serde;
 
 pub(crate) mod buildkite_api;
+pub mod config;
 pub(crate) mod database;
 pub mod errors;
 pub mod workflow;
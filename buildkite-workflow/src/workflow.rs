@@ -1,76 +1,197 @@
 use crate::buildkite_api::BuildkiteAPI;
+use crate::buildkite_api::errors::Error as ApiError;
+use crate::config::Config;
 use crate::database::models::Pipeline;
-use crate::database::DbContext;
+use crate::database::{self, DbContext};
 use crate::errors::Error;
-use alfred::Item;
+use alfred::{Item, Modifier};
+use alfred_workflow::http::ClientBuilder;
+use alfred_workflow::output::Record;
+use alfred_workflow::search::SearchQuery;
+use alfred_workflow::secret::Secret;
+use alfred_workflow::workflow;
+use alfred_workflow::{changes, fts, fuzzy, profile, refresh};
+use log::info;
+use rusqlite::Connection;
+use std::time::Duration;
 
-pub struct Workflow<'a> {
-    api_key: &'a str,
+/// Hosts the workflow's results link to, which are the only ones it will open.
+pub const ALLOWED_HOSTS: &[&str] = &["buildkite.com"];
+
+/// An account the cache is refreshed from, whose name its rows are cached under.
+pub struct Profile {
+    pub name: String,
+    pub api_key: Secret,
+    pub api_url: String,
+    pub http: ClientBuilder,
+}
+
+pub struct Workflow {
+    profiles: Vec<Profile>,
     db: DbContext,
 }
 
-impl<'a> Workflow<'a> {
-    /// Create a new Workflow
+impl Workflow {
+    /// Create a new Workflow with a single, unnamed, profile.
     ///
     /// # Errors
     ///
     /// Will return `Err` if database connection fails.
     ///
     #[inline]
-    pub fn new(api_key: &'a str, database_url: &str) -> Result<Self, Error> {
-        let db = DbContext::new(database_url)?;
-        Ok(Workflow { api_key, db })
+    pub fn new(
+        api_key: Secret,
+        api_url: &str,
+        database_url: &str,
+        http: ClientBuilder,
+    ) -> Result<Self, Error> {
+        let profile = Profile {
+            name: String::new(),
+            api_key,
+            api_url: api_url.to_owned(),
+            http,
+        };
+        Self::with_profiles(database_url, vec![profile])
     }
 
-    /// Refreshes DB with all Buildkite information.
+    /// Create a new Workflow refreshing the cache from every profile.
     ///
     /// # Errors
     ///
-    /// Will return `Err` if database connection fails or hitting the `Buildkite` API fails
+    /// Will return `Err` if database connection fails.
     ///
     #[inline]
-    pub fn refresh_cache(&mut self) -> Result<(), Error> {
-        self.db.run_migrations()?;
-        let api = BuildkiteAPI::new(self.api_key);
-        self.db.delete_pipelines()?;
-        for organizations in api.get_organizations_paginated() {
-            for org in organizations? {
-                for pipelines in api.get_pipelines_paginated(&org.slug) {
-                    let pl = pipelines?
-                        .into_iter()
-                        .map(|p| Pipeline {
-                            url: format!("https://buildkite.com/{}/{}", &org.slug, &p.name),
-                            unique_name: format!("{}/{}", &org.slug, &p.name),
-                            name: p.name,
-                        })
-                        .collect::<Vec<Pipeline>>();
-                    self.db.insert_pipelines(&pl)?;
+    pub fn with_profiles(database_url: &str, profiles: Vec<Profile>) -> Result<Self, Error> {
+        let db = DbContext::new(database_url)?;
+        Ok(Workflow { profiles, db })
+    }
+
+    /// Returns the pipelines best matching the query, best first.
+    fn find(&self, text: &str) -> Result<Vec<Pipeline>, Error> {
+        let query = SearchQuery::parse(text);
+        let pipelines = self.db.find_pipelines(&query)?;
+        let selections = self.db.selection_scores(text)?;
+        Ok(fuzzy::rank(
+            &query,
+            pipelines,
+            10,
+            |pipeline| &pipeline.name,
+            |pipeline| selections.get(&pipeline.url).copied().unwrap_or_default(),
+        ))
+    }
+}
+
+fn item(repo: Pipeline) -> Item<'static> {
+    alfred::ItemBuilder::new(repo.unique_name.clone())
+        .uid(profile::uid(&repo.profile, &repo.unique_name))
+        .subtitle(profile::subtitle(&repo.profile, &repo.name))
+        .autocomplete(repo.name)
+        .arg(format!("open {}", repo.url))
+        .arg_mod(
+            Modifier::Command,
+            format!("open {}/builds?state=running", repo.url),
+        )
+        .subtitle_mod(Modifier::Command, "Open running builds")
+        .arg_mod(
+            Modifier::Option,
+            format!("open {}/builds?state=failed", repo.url),
+        )
+        .subtitle_mod(Modifier::Option, "Open failed builds")
+        .arg_mod(Modifier::Control, format!("open {}/settings", repo.url))
+        .subtitle_mod(Modifier::Control, "Open pipeline settings")
+        .quicklook_url(repo.url.clone())
+        .text_copy(repo.url)
+        .text_large_type(repo.unique_name)
+        .into_item()
+}
+
+impl workflow::Workflow for Workflow {
+    type Config = Config;
+    type Error = Error;
+
+    const SERVICE: &'static str = "Buildkite";
+    const RESOURCES: &'static [&'static str] = database::RESOURCES;
+    const SEARCH_INDEX: &'static [fts::Source] = database::SEARCH_INDEX;
+    const TRACKED: &'static [changes::Tracked] = database::TRACKED;
+
+    #[inline]
+    fn allowed_hosts(_config: &Config) -> Vec<String> {
+        ALLOWED_HOSTS.iter().map(|&host| host.to_owned()).collect()
+    }
+
+    #[inline]
+    fn open_command(config: &Config) -> Option<&str> {
+        config.cache.open_command.as_deref()
+    }
+
+    #[inline]
+    fn cache_ttl(config: &Config) -> Duration {
+        config.cache.cache_ttl
+    }
+
+    #[inline]
+    fn stale_after(config: &Config) -> Duration {
+        config.cache.stale_after
+    }
+
+    fn from_config(config: Config) -> Result<Self, Error> {
+        let profiles = config
+            .profiles
+            .into_iter()
+            .map(|profile| Profile {
+                http: profile.http(),
+                name: profile.name,
+                api_key: profile.api_key,
+                api_url: profile.api_url,
+            })
+            .collect();
+        Self::with_profiles(&config.cache.database_url, profiles)
+    }
+
+    #[inline]
+    fn connection(&self) -> &Connection {
+        self.db.conn()
+    }
+
+    #[inline]
+    fn connection_mut(&mut self) -> &mut Connection {
+        self.db.conn_mut()
+    }
+
+    fn fetch(&mut self) -> Result<(), Error> {
+        for profile in &self.profiles {
+            if !profile.name.is_empty() {
+                info!("fetching the {} profile", profile.name);
+            }
+            let client = profile.http.clone().build().map_err(ApiError::from)?;
+            let api_key = profile.api_key.expose().map_err(ApiError::from)?;
+            let api = BuildkiteAPI::new(&client, &profile.api_url, &api_key);
+            for organizations in api.get_organizations_paginated() {
+                for org in organizations? {
+                    let detail = profile::subtitle(&profile.name, &format!("org {}", org.slug));
+                    for pipelines in api.get_pipelines_paginated(&org.slug) {
+                        let pl = pipelines?
+                            .into_iter()
+                            .map(|p| Pipeline {
+                                profile: profile.name.clone(),
+                                url: format!("https://buildkite.com/{}/{}", &org.slug, &p.name),
+                                unique_name: format!("{}/{}", &org.slug, &p.name),
+                                name: p.name,
+                            })
+                            .collect::<Vec<Pipeline>>();
+                        self.db.insert_pipelines(&pl)?;
+                        refresh::report(self.db.conn(), "pipelines", &detail, pl.len())?;
+                    }
                 }
             }
         }
-        // and DB cleanup work
-        self.db.optimize()?;
         Ok(())
     }
 
-    /// Queries the stored information using the given query.
-    ///
-    /// # Errors
-    ///
-    /// Will return `Err` if database connection fails.
-    ///
-    #[inline]
-    pub fn query<'items>(&self, repo_name: &[String]) -> Result<Vec<Item<'items>>, Error> {
-        self.db
-            .find_pipelines(repo_name, 10)?
+    fn query(&mut self, _mode: Option<&str>, query: &str) -> Result<Vec<Record>, Error> {
+        self.find(query)?
             .into_iter()
-            .map(|repo| {
-                Ok(alfred::ItemBuilder::new(repo.unique_name)
-                    .subtitle(repo.name.clone())
-                    .autocomplete(repo.name)
-                    .arg(format!("open {}", repo.url))
-                    .into_item())
-            })
-            .collect::<Result<Vec<_>, _>>()
+            .map(|pipeline| Ok(Record::new(pipeline, item)?))
+            .collect()
     }
 }
@@ -0,0 +1,133 @@
+use alfred_workflow::failure::{Failure, ToFailure};
+use alfred_workflow::http::Client;
+use alfred_workflow::mock_server::{self, MockServer, Route};
+use alfred_workflow::secret::Secret;
+use alfred_workflow::workflow::Workflow as _;
+use buildkite_workflow_lib::workflow::Workflow;
+use std::time::Duration;
+
+const ORGANIZATIONS: &str = include_str!("fixtures/organizations.json");
+const PIPELINES_PAGE_1: &str = include_str!("fixtures/pipelines_page_1.json");
+const PIPELINES_PAGE_2: &str = include_str!("fixtures/pipelines_page_2.json");
+const UNAUTHORIZED: &str = include_str!("fixtures/unauthorized.json");
+
+fn serve_pipelines(server: &MockServer) {
+    let url = server.url();
+    server.route(Route::get("/organizations").json(ORGANIZATIONS));
+    server.route(
+        Route::get("/organizations/acme/pipelines?per_page=100")
+            .header(
+                "Link",
+                &format!(
+                    r#"<{url}/organizations/acme/pipelines?page=2&per_page=100>; rel="next", <{url}/organizations/acme/pipelines?page=2&per_page=100>; rel="last""#
+                ),
+            )
+            .json(PIPELINES_PAGE_1),
+    );
+    server.route(
+        Route::get("/organizations/acme/pipelines?page=2&per_page=100")
+            .header(
+                "Link",
+                &format!(
+                    r#"<{url}/organizations/acme/pipelines?page=1&per_page=100>; rel="first", <{url}/organizations/acme/pipelines?page=1&per_page=100>; rel="prev""#
+                ),
+            )
+            .json(PIPELINES_PAGE_2),
+    );
+}
+
+fn workflow(api_url: &str, database_url: &str) -> Workflow {
+    let http = Client::builder().backoff(Duration::from_millis(10));
+    Workflow::new(Secret::new("token"), api_url, database_url, http).unwrap()
+}
+
+fn query(wf: &mut Workflow, text: &str) -> Vec<String> {
+    wf.query(None, text)
+        .unwrap()
+        .into_iter()
+        .map(|record| record.item.title.into_owned())
+        .collect()
+}
+
+#[test]
+fn refresh_follows_link_headers_then_query_finds_pipelines() {
+    let server = MockServer::start();
+    serve_pipelines(&server);
+    let url = server.url();
+    let db = mock_server::temp_database("buildkite-refresh-links");
+    let mut wf = workflow(&url, db.to_str().unwrap());
+
+    wf.refresh().unwrap();
+
+    assert_eq!(query(&mut wf, "payments"), ["acme/payments"]);
+    assert_eq!(query(&mut wf, "docs"), ["acme/docs-site"]);
+    assert_eq!(query(&mut wf, "").len(), 3);
+    assert_eq!(wf.progress().unwrap(), None);
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 3);
+    assert_eq!(requests[0].header("Authorization"), Some("Bearer token"));
+}
+
+#[test]
+fn refresh_waits_out_rate_limits() {
+    let server = MockServer::start();
+    server.route(
+        Route::get("/organizations")
+            .status(429)
+            .header("RateLimit-Remaining", "0")
+            .header("RateLimit-Reset", "0")
+            .times(1),
+    );
+    serve_pipelines(&server);
+    let url = server.url();
+    let db = mock_server::temp_database("buildkite-refresh-rate-limit");
+    let mut wf = workflow(&url, db.to_str().unwrap());
+
+    wf.refresh().unwrap();
+
+    assert_eq!(query(&mut wf, "ledger"), ["acme/ledger"]);
+    assert_eq!(server.requests().len(), 4);
+}
+
+#[test]
+fn failed_refresh_keeps_the_cached_pipelines() {
+    let server = MockServer::start();
+    serve_pipelines(&server);
+    let url = server.url();
+    let db = mock_server::temp_database("buildkite-refresh-failed");
+    workflow(&url, db.to_str().unwrap()).refresh().unwrap();
+
+    let rejecting = MockServer::start();
+    rejecting.route(Route::get("/organizations").status(401).json(UNAUTHORIZED));
+    let url = rejecting.url();
+    let mut wf = workflow(&url, db.to_str().unwrap());
+
+    let err = wf.refresh().unwrap_err();
+    assert_eq!(
+        err.to_failure(),
+        Failure::Unauthorized {
+            service: "Buildkite",
+            status: 401
+        }
+    );
+    assert_eq!(query(&mut wf, "payments"), ["acme/payments"]);
+    assert_eq!(
+        wf.progress().unwrap(),
+        None,
+        "a failed refresh is no longer running"
+    );
+
+    let status = wf.status().unwrap();
+    assert_eq!(status[0].resource, "pipelines");
+    assert_eq!(status[0].rows, 3);
+    assert!(status[0].refreshed_at.is_some());
+    assert!(status[0].duration.is_some());
+    assert!(status[0].failed_at.is_some());
+    assert_eq!(status[0].error.as_deref(), Some(err.to_string().as_str()));
+    assert_eq!(
+        wf.last_refreshed().unwrap(),
+        status[0].refreshed_at,
+        "a failed refresh keeps the cache's age"
+    );
+}
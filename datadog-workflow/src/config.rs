@@ -0,0 +1,42 @@
+use alfred_workflow::config::{ApiConfig, CacheConfig, Loader, WorkflowConfig};
+use alfred_workflow::secret::Secret;
+
+/// The US API, EU accounts use `https://api.datadoghq.eu/api`.
+const DEFAULT_API_URL: &str = "https://api.datadoghq.com/api";
+
+/// The workflow's configuration, see `alfred_workflow::config` for where it is read from.
+#[derive(Debug)]
+pub struct Config {
+    pub profiles: Vec<ProfileConfig>,
+    pub cache: CacheConfig,
+}
+
+/// The configuration of an account the cache is refreshed from, which Datadog identifies by an
+/// application key too, see `alfred_workflow::config::Loader::profiles`.
+#[derive(Debug)]
+pub struct ProfileConfig {
+    pub api: ApiConfig,
+    pub application_key: Secret,
+    pub subdomain: String,
+}
+
+impl WorkflowConfig for Config {
+    const NAME: &'static str = "datadog";
+
+    fn read(loader: &mut Loader) -> Self {
+        Self {
+            profiles: loader.profiles(ProfileConfig::read),
+            cache: CacheConfig::read(loader),
+        }
+    }
+}
+
+impl ProfileConfig {
+    fn read(loader: &mut Loader, name: &str) -> Self {
+        Self {
+            api: ApiConfig::read(loader, name, DEFAULT_API_URL),
+            application_key: loader.secret("APPLICATION_KEY"),
+            subdomain: loader.required("SUBDOMAIN"),
+        }
+    }
+}
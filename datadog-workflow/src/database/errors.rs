@@ -1,7 +1,20 @@
+use alfred_workflow::failure::{Failure, ToFailure};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum Error {
     #[error(transparent)]
     SQLite(#[from] rusqlite::Error),
+
+    #[error(transparent)]
+    AlfredWorkflow(#[from] alfred_workflow::errors::Error),
+}
+
+impl ToFailure for Error {
+    fn to_failure(&self) -> Failure {
+        match self {
+            Self::AlfredWorkflow(e) => e.to_failure(),
+            Self::SQLite(_) => Failure::Other(self.to_string()),
+        }
+    }
 }
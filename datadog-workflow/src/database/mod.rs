@@ -9,81 +9,276 @@ use crate::database::models::Dashboard;
 use crate::database::monitors::Monitors;
 use crate::database::screenboards::Screenboards;
 use crate::database::timeboards::Timeboards;
+use alfred_workflow::migrations::Migration;
+use alfred_workflow::search::{SearchFields, SearchQuery};
+use alfred_workflow::{changes, failure, frecency, fts, refresh};
 use rusqlite::{Connection, ToSql};
+use std::collections::HashMap;
+
+const MIGRATIONS: &[Migration] = &[
+    Migration::new(
+        1,
+        "create timeboards, screenboards and monitors tables",
+        "CREATE TABLE IF NOT EXISTS timeboards (
+            id          TEXT     NOT NULL PRIMARY KEY,
+            title       TEXT     NOT NULL,
+            description TEXT     NOT NULL,
+            url         TEXT     NOT NULL,
+            modified    DATETIME NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_timeboards_title_modified ON timeboards (title, modified);
+        CREATE TABLE IF NOT EXISTS screenboards (
+            id          INTEGER  NOT NULL PRIMARY KEY,
+            title       TEXT     NOT NULL,
+            description TEXT     NOT NULL,
+            url         TEXT     NOT NULL,
+            modified    DATETIME NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_screenboards_title_modified ON screenboards (title, modified);
+        CREATE TABLE IF NOT EXISTS monitors (
+            id          INTEGER  NOT NULL PRIMARY KEY,
+            name        TEXT     NOT NULL,
+            url         TEXT     NOT NULL,
+            modified    DATETIME NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_monitors_name_modified ON monitors (name, modified);
+        CREATE TABLE IF NOT EXISTS monitor_tags (
+            id          INTEGER NOT NULL,
+            name        TEXT    NOT NULL,
+            CONSTRAINT fk_monitors
+            FOREIGN KEY (id)
+            REFERENCES monitors(id)
+            ON DELETE CASCADE
+        );
+        CREATE INDEX IF NOT EXISTS idx_monitor_tags_id ON monitor_tags (id);
+        CREATE INDEX IF NOT EXISTS idx_monitor_tags_name ON monitor_tags (name);",
+    ),
+    Migration::new(2, "create selections table", frecency::SCHEMA),
+    Migration::new(3, "create refreshes table", refresh::SCHEMA),
+    Migration::new(4, "add refresh duration and error", refresh::OUTCOME_SCHEMA),
+    Migration::new(5, "create search index", fts::SCHEMA),
+    Migration::new(
+        6,
+        "key timeboards, screenboards and monitors by profile",
+        "CREATE TABLE timeboards_profiles (
+            profile     TEXT     NOT NULL DEFAULT '',
+            id          TEXT     NOT NULL,
+            title       TEXT     NOT NULL,
+            description TEXT     NOT NULL,
+            url         TEXT     NOT NULL,
+            modified    DATETIME NOT NULL,
+            first_seen  INTEGER,
+            PRIMARY KEY (profile, id)
+        );
+        INSERT INTO timeboards_profiles (id, title, description, url, modified)
+        SELECT id, title, description, url, modified FROM timeboards;
+        DROP TABLE timeboards;
+        ALTER TABLE timeboards_profiles RENAME TO timeboards;
+        CREATE INDEX idx_timeboards_title_modified ON timeboards (title, modified);
+        CREATE TABLE screenboards_profiles (
+            profile     TEXT     NOT NULL DEFAULT '',
+            id          INTEGER  NOT NULL,
+            title       TEXT     NOT NULL,
+            description TEXT     NOT NULL,
+            url         TEXT     NOT NULL,
+            modified    DATETIME NOT NULL,
+            first_seen  INTEGER,
+            PRIMARY KEY (profile, id)
+        );
+        INSERT INTO screenboards_profiles (id, title, description, url, modified)
+        SELECT id, title, description, url, modified FROM screenboards;
+        DROP TABLE screenboards;
+        ALTER TABLE screenboards_profiles RENAME TO screenboards;
+        CREATE INDEX idx_screenboards_title_modified ON screenboards (title, modified);
+        CREATE TABLE monitors_profiles (
+            profile     TEXT     NOT NULL DEFAULT '',
+            id          INTEGER  NOT NULL,
+            name        TEXT     NOT NULL,
+            url         TEXT     NOT NULL,
+            modified    DATETIME NOT NULL,
+            first_seen  INTEGER,
+            PRIMARY KEY (profile, id)
+        );
+        INSERT INTO monitors_profiles (id, name, url, modified)
+        SELECT id, name, url, modified FROM monitors;
+        CREATE TABLE monitor_tags_profiles (
+            profile     TEXT    NOT NULL DEFAULT '',
+            id          INTEGER NOT NULL,
+            name        TEXT    NOT NULL,
+            CONSTRAINT fk_monitors
+            FOREIGN KEY (profile, id)
+            REFERENCES monitors_profiles(profile, id)
+            ON DELETE CASCADE
+        );
+        INSERT INTO monitor_tags_profiles (id, name) SELECT id, name FROM monitor_tags;
+        DROP TABLE monitor_tags;
+        DROP TABLE monitors;
+        ALTER TABLE monitors_profiles RENAME TO monitors;
+        ALTER TABLE monitor_tags_profiles RENAME TO monitor_tags;
+        CREATE INDEX idx_monitors_name_modified ON monitors (name, modified);
+        CREATE INDEX idx_monitor_tags_id ON monitor_tags (profile, id);
+        CREATE INDEX idx_monitor_tags_name ON monitor_tags (name);
+        INSERT INTO search_entries (resource, key, url, title, description, tags)
+        SELECT * FROM (
+            SELECT 'timeboards' AS resource, rowid AS key, url, fts_words(title) AS title,
+                fts_words(description) AS description, '' AS tags
+            FROM timeboards
+            UNION ALL
+            SELECT 'screenboards', rowid, url, fts_words(title), fts_words(description), ''
+            FROM screenboards
+            UNION ALL
+            SELECT 'monitors', m.rowid, m.url, fts_words(m.name), '',
+                fts_words(coalesce((SELECT group_concat(t.name, ' ') FROM monitor_tags t WHERE t.profile = m.profile AND t.id = m.id), ''))
+            FROM monitors m
+        )
+        ORDER BY length(title), title;
+        INSERT INTO search_index (search_index) VALUES ('rebuild');",
+    ),
+    Migration::new(7, "create refresh progress table", refresh::PROGRESS_SCHEMA),
+];
+
+/// The cached resources, as tracked in the refreshes table.
+pub const RESOURCES: &[&str] = &["timeboards", "screenboards", "monitors"];
+
+/// The tables built while refreshing and swapped in once it succeeds.
+pub const STAGED_TABLES: &[&str] = &["timeboards", "screenboards", "monitors", "monitor_tags"];
+
+/// The resources whose changes each refresh summarizes.
+pub const TRACKED: &[changes::Tracked] = &[
+    changes::Tracked {
+        resource: "timeboards",
+        noun: "timeboard",
+        title: "title",
+        profile: Some("profile"),
+    },
+    changes::Tracked {
+        resource: "screenboards",
+        noun: "screenboard",
+        title: "title",
+        profile: Some("profile"),
+    },
+    changes::Tracked {
+        resource: "monitors",
+        noun: "monitor",
+        title: "name",
+        profile: Some("profile"),
+    },
+];
+
+/// The resources indexed for full-text search, their titles, descriptions and tags, keyed by
+/// rowid as an id may be cached for several profiles.
+pub const SEARCH_INDEX: &[fts::Source] = &[
+    fts::Source {
+        resource: "timeboards",
+        select: "SELECT rowid AS key, url, title, description, '' AS tags FROM timeboards",
+    },
+    fts::Source {
+        resource: "screenboards",
+        select: "SELECT rowid AS key, url, title, description, '' AS tags FROM screenboards",
+    },
+    fts::Source {
+        resource: "monitors",
+        select: "SELECT m.rowid AS key, m.url, m.name AS title, '' AS description,
+            coalesce((SELECT group_concat(t.name, ' ') FROM monitor_tags t WHERE t.profile = m.profile AND t.id = m.id), '') AS tags
+            FROM monitors m",
+    },
+];
+
+/// Search fields shared by timeboards, screenboards and dashboards.
+pub(crate) const BOARD_SEARCH_FIELDS: SearchFields = SearchFields {
+    default: &["title"],
+    qualifiers: &[("title", "title"), ("desc", "description")],
+    profile: Some("profile"),
+};
 
 #[derive(Debug)]
 pub struct DbContext {
     conn: Connection,
-    subdomain: String,
 }
 
 impl DbContext {
     #[inline]
-    pub fn new(database_url: &str, subdomain: String) -> Result<Self, Error> {
-        let conn = Connection::open(database_url)?;
-        Ok(DbContext { conn, subdomain })
+    pub fn new(database_url: &str) -> Result<Self, Error> {
+        let conn = alfred_workflow::open_database_at(database_url, MIGRATIONS)?;
+        Ok(DbContext { conn })
     }
 
     // TODO: make interior mutable instead of everything having to be mutable
 
     #[inline]
-    pub fn monitors(&mut self) -> Monitors {
+    pub fn monitors(&mut self) -> Monitors<'_> {
         Monitors::new(self)
     }
 
     #[inline]
-    pub fn timeboards(&mut self) -> Timeboards {
+    pub fn timeboards(&mut self) -> Timeboards<'_> {
         Timeboards::new(self)
     }
 
     #[inline]
-    pub fn screenboards(&mut self) -> Screenboards {
+    pub fn screenboards(&mut self) -> Screenboards<'_> {
         Screenboards::new(self)
     }
 
     #[inline]
-    pub fn find_dashboard(&self, title: &str, limit: i64) -> Result<Vec<Dashboard>, Error> {
-        // This will allow searching by full name or just the words within the name;
-        // it's not a regex but it's good enough.
-        let query = format!(
-            "%{}%",
-            title
-                .split_terminator(' ')
-                .flat_map(|s| s.split_terminator('_'))
-                .flat_map(|s| s.split_terminator('-'))
-                .collect::<Vec<&str>>()
-                .join("%")
+    pub fn find_dashboard(&self, query: &SearchQuery) -> Result<Vec<Dashboard>, Error> {
+        let search = fts::compile(
+            query,
+            &["timeboards", "screenboards"],
+            "fts.resource = boards.resource AND fts.key = boards.key",
+            &BOARD_SEARCH_FIELDS,
         );
+        let params = search
+            .params
+            .iter()
+            .map(|p| p as &dyn ToSql)
+            .collect::<Vec<_>>();
 
-        self.conn.prepare(
-            "SELECT title, description, url, modified FROM timeboards WHERE title LIKE ?1
-                         UNION ALL
-                         SELECT title, description, url, modified FROM screenboards WHERE title LIKE ?1
-                         ORDER BY modified DESC
-                         LIMIT ?",
-        )?.query_map([&query as &dyn ToSql,&limit], |row| {
-            Ok(Dashboard{
-                title:row.get(0)?,
-                description:row.get(1)?,
-                url: row.get(2)?,
-            })
-        })?.map(|r|{
-            Ok(r?)
-        }).collect::<Result<Vec<_>, _>>()
+        let dashboards = self
+            .conn
+            .prepare(&format!(
+                "SELECT profile, title, description, url, modified FROM (
+                SELECT 'timeboards' AS resource, rowid AS key, profile, title, description, url, modified FROM timeboards
+                UNION ALL
+                SELECT 'screenboards', rowid, profile, title, description, url, modified FROM screenboards
+            ) boards
+            {}
+            WHERE {}
+            ORDER BY {} LIMIT {}",
+                search.join,
+                search.clause,
+                search.order_by("modified DESC"),
+                fts::CANDIDATES
+            ))?
+            .query_map(&*params, |row| {
+                Ok(Dashboard {
+                    profile: row.get(0)?,
+                    title: row.get(1)?,
+                    description: row.get(2)?,
+                    url: row.get(3)?,
+                })
+            })?
+            .map(|r| Ok(r?))
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        if dashboards.is_empty() {
+            failure::ensure_cached(&self.conn, &["timeboards", "screenboards"])?;
+        }
+        Ok(dashboards)
+    }
+
+    #[inline]
+    pub fn selection_scores(&self, query: &str) -> Result<HashMap<String, i64>, Error> {
+        Ok(frecency::scores(&self.conn, query)?)
     }
 
     #[inline]
-    pub fn run_migrations(&mut self) -> Result<(), Error> {
-        self.timeboards().run_migrations()?;
-        self.screenboards().run_migrations()?;
-        self.monitors().run_migrations()?;
-        Ok(())
+    pub const fn conn(&self) -> &Connection {
+        &self.conn
     }
 
     #[inline]
-    pub fn optimize(&self) -> Result<(), Error> {
-        // since this workflow is READ heavy, let's optimize the SQLite indexes and DB
-        self.conn.execute("VACUUM;", [])?;
-        Ok(())
+    pub const fn conn_mut(&mut self) -> &mut Connection {
+        &mut self.conn
     }
 }
@@ -8,8 +8,9 @@ pub struct InsertMonitor {
     pub modified: DateTime<Utc>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct Monitor {
+    pub profile: String,
     pub id: i32,
     pub name: String,
     pub url: String,
@@ -24,8 +25,9 @@ pub struct InsertTimeBoard {
     pub modified: DateTime<Utc>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct TimeBoard {
+    pub profile: String,
     pub id: String,
     pub title: String,
     pub description: String,
@@ -41,8 +43,9 @@ pub struct InsertScreenBoard {
     pub modified: DateTime<Utc>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct ScreenBoard {
+    pub profile: String,
     pub id: i32,
     pub title: String,
     pub description: String,
@@ -50,8 +53,9 @@ pub struct ScreenBoard {
     pub modified: DateTime<Utc>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct Dashboard {
+    pub profile: String,
     pub title: String,
     pub description: String,
     pub url: String,
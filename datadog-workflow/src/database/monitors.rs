@@ -1,8 +1,23 @@
+use crate::database::DbContext;
 use crate::database::errors::Error;
 use crate::database::models::{InsertMonitor, Monitor};
-use crate::database::DbContext;
+use alfred_workflow::search::{SearchFields, SearchQuery};
+use alfred_workflow::{failure, fts};
+use log::debug;
 use rusqlite::ToSql;
 
+const SEARCH_FIELDS: SearchFields = SearchFields {
+    default: &["m.name"],
+    qualifiers: &[
+        ("name", "m.name"),
+        (
+            "tag",
+            "coalesce((SELECT group_concat(t.name, ' ') FROM monitor_tags t WHERE t.profile = m.profile AND t.id = m.id), '')",
+        ),
+    ],
+    profile: Some("m.profile"),
+};
+
 pub struct Monitors<'a> {
     db: &'a mut DbContext,
 }
@@ -14,110 +29,76 @@ impl<'a> Monitors<'a> {
     }
 
     #[inline]
-    pub fn run_migrations(&self) -> Result<(), Error> {
-        self.db.conn.execute_batch(
-            "CREATE TABLE IF NOT EXISTS monitors (
-                id          INTEGER  NOT NULL PRIMARY KEY,
-                name        TEXT     NOT NULL,
-                url         TEXT     NOT NULL,
-                modified    DATETIME NOT NULL
-            );
-            CREATE INDEX IF NOT EXISTS idx_monitors_name_modified ON monitors (name, modified);
-            CREATE TABLE IF NOT EXISTS monitor_tags (
-                id          INTEGER NOT NULL,
-                name        TEXT    NOT NULL,
-                CONSTRAINT fk_monitors
-                FOREIGN KEY (id)
-                REFERENCES monitors(id)
-                ON DELETE CASCADE
-            );
-            CREATE INDEX IF NOT EXISTS idx_monitor_tags_id ON monitor_tags (id);
-            CREATE INDEX IF NOT EXISTS idx_monitor_tags_name ON monitor_tags (name);",
-        )?;
-        Ok(())
-    }
-
-    #[inline]
-    pub fn delete_all(&self) -> Result<(), Error> {
-        self.db.conn.execute("DELETE FROM monitors;", [])?;
-        Ok(())
-    }
-
-    #[inline]
-    pub fn insert(&mut self, monitors: &[InsertMonitor]) -> Result<(), Error> {
+    pub fn insert(
+        &mut self,
+        profile: &str,
+        host: &str,
+        monitors: &[InsertMonitor],
+    ) -> Result<(), Error> {
         let tx = self.db.conn.transaction()?;
-        let mut stmt_monitor =
-            tx.prepare("INSERT INTO monitors (id, name, url, modified) VALUES (?1, ?2, ?3, ?4)")?;
-        let mut stmt_tags = tx.prepare("INSERT INTO monitor_tags (id, name) VALUES (?1, ?2)")?;
+        let mut stmt_monitor = tx.prepare(
+            "INSERT INTO monitors_staging (profile, id, name, url, modified) VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?;
+        let mut stmt_tags =
+            tx.prepare("INSERT INTO monitor_tags_staging (profile, id, name) VALUES (?1, ?2, ?3)")?;
 
         for monitor in monitors {
-            let url = format!(
-                "https://{}.datadoghq.com/monitors/{}",
-                self.db.subdomain, monitor.id
-            );
+            let url = format!("https://{host}/monitors/{}", monitor.id);
             stmt_monitor.execute([
-                &monitor.id as &dyn ToSql,
+                &profile as &dyn ToSql,
+                &monitor.id,
                 &monitor.name,
                 &url,
                 &monitor.modified,
             ])?;
             for tag in &monitor.tags {
-                stmt_tags.execute([&monitor.id as &dyn ToSql, &tag])?;
+                stmt_tags.execute([&profile as &dyn ToSql, &monitor.id, &tag])?;
             }
         }
 
         stmt_monitor.finalize()?;
         stmt_tags.finalize()?;
         tx.commit()?;
+        debug!("staged {} monitors", monitors.len());
         Ok(())
     }
 
     #[inline]
-    pub fn find(&self, name: &str, tag: Option<&str>, limit: i64) -> Result<Vec<Monitor>, Error> {
-        // This will allow searching by full name or just the words within the name;
-        // it's not a regex but it's good enough.
-        let query = format!(
-            "%{}%",
-            name.split_terminator(' ')
-                .flat_map(|s| s.split_terminator('_'))
-                .flat_map(|s| s.split_terminator('-'))
-                .collect::<Vec<&str>>()
-                .join("%")
-        );
+    pub fn find(&self, query: &SearchQuery) -> Result<Vec<Monitor>, Error> {
+        let search = fts::compile(query, &["monitors"], "fts.key = m.rowid", &SEARCH_FIELDS);
+        let params = search
+            .params
+            .iter()
+            .map(|p| p as &dyn ToSql)
+            .collect::<Vec<_>>();
 
-        let tag_query: String;
-        let mut params: Vec<&dyn ToSql> = vec![&query];
-        let mut select = "SELECT m.id, m.name, m.url, m.modified FROM monitors m ".to_owned();
-        match tag {
-            Some(t) => {
-                select += "LEFT JOIN monitor_tags t ON t.id = m.id WHERE m.name LIKE ? AND t.name LIKE ? ";
-                tag_query = format!(
-                    "%{}%",
-                    t.split_terminator(' ')
-                        .flat_map(|s| s.split_terminator('_'))
-                        .flat_map(|s| s.split_terminator('-'))
-                        .collect::<Vec<&str>>()
-                        .join("%")
-                );
-                params.push(&tag_query);
-            }
-            _ => select += "WHERE m.name LIKE ? ",
-        }
-        select += "ORDER BY m.modified DESC LIMIT ?";
-        params.push(&limit);
+        let select = format!(
+            "SELECT m.profile, m.id, m.name, m.url, m.modified FROM monitors m {} WHERE {} ORDER BY {} LIMIT {}",
+            search.join,
+            search.clause,
+            search.order_by("m.modified DESC"),
+            fts::CANDIDATES
+        );
 
-        self.db
+        let monitors = self
+            .db
             .conn
             .prepare(&select)?
             .query_map(&*params, |row| {
                 Ok(Monitor {
-                    id: row.get(0)?,
-                    name: row.get(1)?,
-                    url: row.get(2)?,
-                    modified: row.get(3)?,
+                    profile: row.get(0)?,
+                    id: row.get(1)?,
+                    name: row.get(2)?,
+                    url: row.get(3)?,
+                    modified: row.get(4)?,
                 })
             })?
             .map(|r| Ok(r?))
-            .collect::<Result<Vec<_>, _>>()
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        if monitors.is_empty() {
+            failure::ensure_cached(&self.db.conn, &["monitors"])?;
+        }
+        Ok(monitors)
     }
 }
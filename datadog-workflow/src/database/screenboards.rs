@@ -1,6 +1,9 @@
 use crate::database::errors::Error;
 use crate::database::models::{InsertScreenBoard, ScreenBoard};
-use crate::database::DbContext;
+use crate::database::{BOARD_SEARCH_FIELDS, DbContext};
+use alfred_workflow::search::SearchQuery;
+use alfred_workflow::{failure, fts};
+use log::debug;
 use rusqlite::ToSql;
 
 pub struct Screenboards<'a> {
@@ -14,76 +17,69 @@ impl<'a> Screenboards<'a> {
     }
 
     #[inline]
-    pub fn run_migrations(&self) -> Result<(), Error> {
-        self.db.conn.execute_batch(
-            "CREATE TABLE IF NOT EXISTS screenboards (
-                id          INTEGER  NOT NULL PRIMARY KEY,
-                title       TEXT     NOT NULL,
-                description TEXT     NOT NULL,
-                url         TEXT     NOT NULL,
-                modified    DATETIME NOT NULL
-            );
-            CREATE INDEX IF NOT EXISTS idx_screenboards_title_modified ON screenboards (title, modified);",
-        )?;
-        Ok(())
-    }
-
-    #[inline]
-    pub fn delete_all(&self) -> Result<(), Error> {
-        self.db.conn.execute("DELETE FROM screenboards;", [])?;
-        Ok(())
-    }
-
-    #[inline]
-    pub fn insert(&mut self, screenboards: &[InsertScreenBoard]) -> Result<(), Error> {
+    pub fn insert(
+        &mut self,
+        profile: &str,
+        host: &str,
+        screenboards: &[InsertScreenBoard],
+    ) -> Result<(), Error> {
         let tx = self.db.conn.transaction()?;
-        let mut stmt = tx.prepare("INSERT INTO screenboards (id, title, description, url, modified) VALUES (?1, ?2, ?3, ?4, ?5)")?;
+        let mut stmt = tx.prepare("INSERT INTO screenboards_staging (profile, id, title, description, url, modified) VALUES (?1, ?2, ?3, ?4, ?5, ?6)")?;
 
         for board in screenboards {
-            let url = format!(
-                "https://{}.datadoghq.com/screen/{}",
-                self.db.subdomain, board.id
-            );
+            let url = format!("https://{host}/screen/{}", board.id);
             stmt.execute([
-                &board.id as &dyn ToSql,
+                &profile as &dyn ToSql,
+                &board.id,
                 &board.title,
                 &board.description.clone().unwrap_or_default(),
                 &url,
-                &board.modified.timestamp(),
+                &board.modified,
             ])?;
         }
 
         stmt.finalize()?;
         tx.commit()?;
+        debug!("staged {} screenboards", screenboards.len());
         Ok(())
     }
 
     #[inline]
-    pub fn find(&self, title: &str, limit: i64) -> Result<Vec<ScreenBoard>, Error> {
-        // This will allow searching by full name or just the words within the name;
-        // it's not a regex but it's good enough.
-        let query = format!(
-            "%{}%",
-            title
-                .split_terminator(' ')
-                .flat_map(|s| s.split_terminator('_'))
-                .flat_map(|s| s.split_terminator('-'))
-                .collect::<Vec<&str>>()
-                .join("%")
+    pub fn find(&self, query: &SearchQuery) -> Result<Vec<ScreenBoard>, Error> {
+        let search = fts::compile(
+            query,
+            &["screenboards"],
+            "fts.key = screenboards.rowid",
+            &BOARD_SEARCH_FIELDS,
         );
+        let params = search
+            .params
+            .iter()
+            .map(|p| p as &dyn ToSql)
+            .collect::<Vec<_>>();
 
-        self.db.conn.prepare(
-            "SELECT id, title, description, url, modified FROM screenboards WHERE title LIKE ? ORDER BY modified DESC LIMIT ?",
-        )?.query_map([&query as &dyn ToSql,&limit], |row| {
-            Ok(ScreenBoard{
-                id: row.get(0)?,
-                title:row.get(1)?,
-                description:row.get(2)?,
-                url: row.get(3)?,
-                modified:row.get(4)?,
+        let boards = self.db.conn.prepare(&format!(
+            "SELECT profile, id, title, description, url, modified FROM screenboards {} WHERE {} ORDER BY {} LIMIT {}",
+            search.join,
+            search.clause,
+            search.order_by("modified DESC"),
+            fts::CANDIDATES
+        ))?.query_map(&*params, |row| {
+            Ok(ScreenBoard {
+                profile: row.get(0)?,
+                id: row.get(1)?,
+                title: row.get(2)?,
+                description: row.get(3)?,
+                url: row.get(4)?,
+                modified: row.get(5)?,
             })
         })?.map(|r|{
             Ok(r?)
-        }).collect::<Result<Vec<_>, _>>()
+        }).collect::<Result<Vec<_>, Error>>()?;
+
+        if boards.is_empty() {
+            failure::ensure_cached(&self.db.conn, &["screenboards"])?;
+        }
+        Ok(boards)
     }
 }
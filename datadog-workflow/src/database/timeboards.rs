@@ -1,6 +1,9 @@
 use crate::database::errors::Error;
 use crate::database::models::{InsertTimeBoard, TimeBoard};
-use crate::database::DbContext;
+use crate::database::{BOARD_SEARCH_FIELDS, DbContext};
+use alfred_workflow::search::SearchQuery;
+use alfred_workflow::{failure, fts};
+use log::debug;
 use rusqlite::ToSql;
 
 pub struct Timeboards<'a> {
@@ -14,38 +17,20 @@ impl<'a> Timeboards<'a> {
     }
 
     #[inline]
-    pub fn run_migrations(&self) -> Result<(), Error> {
-        self.db.conn.execute_batch(
-            "CREATE TABLE IF NOT EXISTS timeboards (
-                id          TEXT     NOT NULL PRIMARY KEY,
-                title       TEXT     NOT NULL,
-                description TEXT     NOT NULL,
-                url         TEXT     NOT NULL,
-                modified    DATETIME NOT NULL
-            );
-            CREATE INDEX IF NOT EXISTS idx_timeboards_title_modified ON timeboards (title, modified);",
-        )?;
-        Ok(())
-    }
-
-    #[inline]
-    pub fn delete_all(&self) -> Result<(), Error> {
-        self.db.conn.execute("DELETE FROM timeboards;", [])?;
-        Ok(())
-    }
-
-    #[inline]
-    pub fn insert(&mut self, timeboards: &[InsertTimeBoard]) -> Result<(), Error> {
+    pub fn insert(
+        &mut self,
+        profile: &str,
+        host: &str,
+        timeboards: &[InsertTimeBoard],
+    ) -> Result<(), Error> {
         let tx = self.db.conn.transaction()?;
-        let mut stmt = tx.prepare("INSERT INTO timeboards (id, title, description, url, modified) VALUES (?1, ?2, ?3, ?4, ?5)")?;
+        let mut stmt = tx.prepare("INSERT INTO timeboards_staging (profile, id, title, description, url, modified) VALUES (?1, ?2, ?3, ?4, ?5, ?6)")?;
 
         for board in timeboards {
-            let url = format!(
-                "https://{}.datadoghq.com/dash/{}",
-                self.db.subdomain, board.id
-            );
+            let url = format!("https://{host}/dash/{}", board.id);
             stmt.execute([
-                &board.id as &dyn ToSql,
+                &profile as &dyn ToSql,
+                &board.id,
                 &board.title,
                 &board.description.clone().unwrap_or_default(),
                 &url,
@@ -55,35 +40,46 @@ impl<'a> Timeboards<'a> {
 
         stmt.finalize()?;
         tx.commit()?;
+        debug!("staged {} timeboards", timeboards.len());
         Ok(())
     }
 
     #[inline]
-    pub fn find(&self, title: &str, limit: i64) -> Result<Vec<TimeBoard>, Error> {
-        // This will allow searching by full name or just the words within the name;
-        // it's not a regex but it's good enough.
-        let query = format!(
-            "%{}%",
-            title
-                .split_terminator(' ')
-                .flat_map(|s| s.split_terminator('_'))
-                .flat_map(|s| s.split_terminator('-'))
-                .collect::<Vec<&str>>()
-                .join("%")
+    pub fn find(&self, query: &SearchQuery) -> Result<Vec<TimeBoard>, Error> {
+        let search = fts::compile(
+            query,
+            &["timeboards"],
+            "fts.key = timeboards.rowid",
+            &BOARD_SEARCH_FIELDS,
         );
+        let params = search
+            .params
+            .iter()
+            .map(|p| p as &dyn ToSql)
+            .collect::<Vec<_>>();
 
-        self.db.conn.prepare(
-            "SELECT id, title, description, url, modified FROM timeboards WHERE title LIKE ? ORDER BY modified DESC LIMIT ?",
-        )?.query_map([&query as &dyn ToSql,&limit], |row| {
+        let boards = self.db.conn.prepare(&format!(
+            "SELECT profile, id, title, description, url, modified FROM timeboards {} WHERE {} ORDER BY {} LIMIT {}",
+            search.join,
+            search.clause,
+            search.order_by("modified DESC"),
+            fts::CANDIDATES
+        ))?.query_map(&*params, |row| {
             Ok(TimeBoard {
-                id: row.get(0)?,
-                title:row.get(1)?,
-                description:row.get(2)?,
-                url:row.get(3)?,
-                modified:row.get(4)?,
+                profile: row.get(0)?,
+                id: row.get(1)?,
+                title: row.get(2)?,
+                description: row.get(3)?,
+                url: row.get(4)?,
+                modified: row.get(5)?,
             })
         })?.map(|r|{
             Ok(r?)
-        }).collect::<Result<Vec<_>, _>>()
+        }).collect::<Result<Vec<_>, Error>>()?;
+
+        if boards.is_empty() {
+            failure::ensure_cached(&self.db.conn, &["timeboards"])?;
+        }
+        Ok(boards)
     }
 }
@@ -1,6 +1,7 @@
 use crate::database::models::{InsertMonitor, InsertScreenBoard, InsertTimeBoard};
 use crate::errors::Error;
-use reqwest::blocking::Client;
+use alfred_workflow::http::{self, Client};
+use log::debug;
 
 const APPLICATION_KEY: &str = "application_key";
 const API_KEY: &str = "api_key";
@@ -9,19 +10,17 @@ pub struct Api<'a> {
     key: &'a str,
     application_key: &'a str,
     url: &'a str,
-    subdomain: &'a str,
     client: Client,
 }
 
 impl<'a> Api<'a> {
     #[inline]
-    pub fn new(key: &'a str, application_key: &'a str, url: &'a str, subdomain: &'a str) -> Self {
+    pub const fn new(client: Client, key: &'a str, application_key: &'a str, url: &'a str) -> Self {
         Self {
             key,
             application_key,
             url,
-            subdomain,
-            client: reqwest::blocking::Client::new(),
+            client,
         }
     }
 
@@ -32,13 +31,18 @@ impl<'a> Api<'a> {
             #[serde(rename = "dashes")]
             boards: Vec<InsertTimeBoard>,
         }
+        let request = self
+            .client
+            .get(&format!("{}/v1/dash", self.url))
+            .query(&[(APPLICATION_KEY, self.application_key), (API_KEY, self.key)]);
         let results = self
             .client
-            .get(format!("{}/v1/dash", self.url))
-            .query(&[(APPLICATION_KEY, self.application_key), (API_KEY, self.key)])
-            .send()?
+            .send(request)?
+            .error_for_status()
+            .map_err(http::without_query)?
             .json::<Dashboards>()?
             .boards;
+        debug!("fetched {} timeboards", results.len());
         Ok(results)
     }
 
@@ -50,24 +54,34 @@ impl<'a> Api<'a> {
             boards: Vec<InsertScreenBoard>,
         }
 
+        let request = self
+            .client
+            .get(&format!("{}/v1/screen", self.url))
+            .query(&[(APPLICATION_KEY, self.application_key), (API_KEY, self.key)]);
         let results = self
             .client
-            .get(format!("{}/v1/screen", self.url))
-            .query(&[(APPLICATION_KEY, self.application_key), (API_KEY, self.key)])
-            .send()?
+            .send(request)?
+            .error_for_status()
+            .map_err(http::without_query)?
             .json::<ScreenBoards>()?
             .boards;
+        debug!("fetched {} screenboards", results.len());
         Ok(results)
     }
 
     #[inline]
     pub fn get_monitors(&self) -> Result<Vec<InsertMonitor>, Error> {
+        let request = self
+            .client
+            .get(&format!("{}/v1/monitor", self.url))
+            .query(&[(APPLICATION_KEY, self.application_key), (API_KEY, self.key)]);
         let results = self
             .client
-            .get(format!("{}/v1/monitor", self.url))
-            .query(&[(APPLICATION_KEY, self.application_key), (API_KEY, self.key)])
-            .send()?
+            .send(request)?
+            .error_for_status()
+            .map_err(http::without_query)?
             .json::<Vec<InsertMonitor>>()?;
+        debug!("fetched {} monitors", results.len());
         Ok(results)
     }
 }
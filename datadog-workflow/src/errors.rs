@@ -1,3 +1,5 @@
+use alfred_workflow::failure::{Failure, ToFailure};
+use reqwest::StatusCode;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -7,4 +9,25 @@ pub enum Error {
 
     #[error(transparent)]
     SQLite(#[from] crate::database::errors::Error),
+
+    #[error(transparent)]
+    AlfredWorkflow(#[from] alfred_workflow::errors::Error),
+}
+
+impl ToFailure for Error {
+    fn to_failure(&self) -> Failure {
+        match self {
+            Self::Request(e) => match e.status() {
+                Some(status @ (StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN)) => {
+                    Failure::Unauthorized {
+                        service: "Datadog",
+                        status: status.as_u16(),
+                    }
+                }
+                _ => Failure::Other(self.to_string()),
+            },
+            Self::SQLite(e) => e.to_failure(),
+            Self::AlfredWorkflow(e) => e.to_failure(),
+        }
+    }
 }
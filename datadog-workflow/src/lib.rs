@@ -1,6 +1,7 @@
 #[macro_use]
 extern crate serde;
 
+pub mod config;
 pub(crate) mod database;
 pub(crate) mod datadog;
 pub mod errors;
@@ -1,161 +1,363 @@
-use crate::database::DbContext;
+use crate::config::Config;
+use crate::database::models::{Dashboard, Monitor, ScreenBoard, TimeBoard};
+use crate::database::{self, DbContext};
 use crate::datadog::Api;
 use crate::errors::Error;
-use alfred::Item;
+use alfred::{Item, Modifier};
+use alfred_workflow::http::ClientBuilder;
+use alfred_workflow::output::Record;
+use alfred_workflow::search::SearchQuery;
+use alfred_workflow::secret::Secret;
+use alfred_workflow::workflow::{self, Mode};
+use alfred_workflow::{changes, fts, fuzzy, opener, profile, refresh};
+use log::info;
+use rusqlite::Connection;
+use serde::Serialize;
 use std::str;
+use std::time::Duration;
 
-pub struct Workflow<'a> {
-    api_key: &'a str,
-    application_key: &'a str,
-    api_url: &'a str,
+/// Hosts the workflow's results link to, which are the only ones it will open: the Datadog
+/// sites, eg. `us5.datadoghq.com`, and US1-FED.
+pub const ALLOWED_HOSTS: &[&str] = &["datadoghq.com", "datadoghq.eu", "ddog-gov.com"];
+
+/// The site of profiles whose API is not on a Datadog one, eg. behind a proxy.
+const DEFAULT_SITE: &str = "datadoghq.com";
+
+const MODE_DASHBOARDS: &str = "d";
+const MODE_TIMEBOARDS: &str = "t";
+const MODE_SCREENBOARDS: &str = "s";
+const MODE_MONITORS: &str = "m";
+
+/// The option filtering monitors by a tag, as `tag:<tag>` in the query also does.
+const TAG_OPTION: &str = "--tag";
+
+/// An account the cache is refreshed from, whose name its rows are cached under.
+pub struct Profile {
+    pub name: String,
+    pub api_key: Secret,
+    pub application_key: Secret,
+    pub api_url: String,
+    pub subdomain: String,
+    pub http: ClientBuilder,
+}
+
+impl Profile {
+    /// Returns the host of the account's dashboards and monitors, on the site of its API.
+    fn app_host(&self) -> String {
+        let site = opener::site_host(&self.api_url).filter(|site| {
+            ALLOWED_HOSTS
+                .iter()
+                .any(|allowed| opener::is_within(site, allowed))
+        });
+        format!(
+            "{}.{}",
+            self.subdomain,
+            site.as_deref().unwrap_or(DEFAULT_SITE)
+        )
+    }
+}
+
+pub struct Workflow {
+    profiles: Vec<Profile>,
     db: DbContext,
-    subdomain: &'a str,
 }
 
-impl<'a> Workflow<'a> {
-    /// Creates a new `DataDog` workflow for use.
+impl Workflow {
+    /// Creates a new `DataDog` workflow for use, with a single unnamed profile.
     ///
     /// # Errors
     /// Can return when database error occurs.
     #[inline]
     pub fn new(
-        api_key: &'a str,
-        application_key: &'a str,
+        api_key: Secret,
+        application_key: Secret,
         database_url: &str,
-        api_url: &'a str,
-        subdomain: &'a str,
+        api_url: &str,
+        subdomain: &str,
+        http: ClientBuilder,
     ) -> Result<Self, Error> {
-        let db = DbContext::new(database_url, subdomain.to_owned())?;
-        Ok(Workflow {
+        let profile = Profile {
+            name: String::new(),
             api_key,
             application_key,
-            api_url,
-            db,
-            subdomain,
-        })
+            api_url: api_url.to_owned(),
+            subdomain: subdomain.to_owned(),
+            http,
+        };
+        Self::with_profiles(database_url, vec![profile])
     }
 
-    /// Refreshes cached data from `DataDog` API
+    /// Creates a new `DataDog` workflow refreshing the cache from every profile.
     ///
     /// # Errors
-    /// can return when database error occurs or API.
-    pub fn refresh_cache(&mut self) -> Result<(), Error> {
-        let datadog_api = Api::new(
-            self.api_key,
-            self.application_key,
-            self.api_url,
-            self.subdomain,
-        );
-        self.db.run_migrations()?;
-        self.refresh_timeboards(&datadog_api)?;
-        self.refresh_screenboards(&datadog_api)?;
-        self.refresh_monitors(&datadog_api)?;
+    /// Can return when database error occurs.
+    #[inline]
+    pub fn with_profiles(database_url: &str, profiles: Vec<Profile>) -> Result<Self, Error> {
+        let db = DbContext::new(database_url)?;
+        Ok(Workflow { profiles, db })
+    }
 
-        // and DB cleanup work
-        Ok(self.db.optimize()?)
+    fn refresh_all(&mut self) -> Result<(), Error> {
+        for profile in &self.profiles {
+            if !profile.name.is_empty() {
+                info!("fetching the {} profile", profile.name);
+            }
+            let client = profile.http.clone().build()?;
+            let api_key = profile.api_key.expose()?;
+            let application_key = profile.application_key.expose()?;
+            let datadog_api = Api::new(client, &api_key, &application_key, &profile.api_url);
+            Self::refresh_timeboards(&mut self.db, profile, &datadog_api)?;
+            Self::refresh_screenboards(&mut self.db, profile, &datadog_api)?;
+            Self::refresh_monitors(&mut self.db, profile, &datadog_api)?;
+        }
+        Ok(())
     }
 
-    fn refresh_timeboards(&mut self, datadog_api: &Api) -> Result<(), Error> {
-        let mut db = self.db.timeboards();
-        db.delete_all()?;
+    fn refresh_timeboards(
+        db: &mut DbContext,
+        profile: &Profile,
+        datadog_api: &Api,
+    ) -> Result<(), Error> {
         let results = datadog_api.get_timeboards()?;
-        db.insert(&results)?;
+        db.timeboards()
+            .insert(&profile.name, &profile.app_host(), &results)?;
+        refresh::report(db.conn(), "timeboards", &profile.name, results.len())?;
         Ok(())
     }
 
-    fn refresh_screenboards(&mut self, datadog_api: &Api) -> Result<(), Error> {
-        let mut db = self.db.screenboards();
-        db.delete_all()?;
+    fn refresh_screenboards(
+        db: &mut DbContext,
+        profile: &Profile,
+        datadog_api: &Api,
+    ) -> Result<(), Error> {
         let results = datadog_api.get_screenboards()?;
-        db.insert(&results)?;
+        db.screenboards()
+            .insert(&profile.name, &profile.app_host(), &results)?;
+        refresh::report(db.conn(), "screenboards", &profile.name, results.len())?;
         Ok(())
     }
 
-    fn refresh_monitors(&mut self, datadog_api: &Api) -> Result<(), Error> {
-        let mut db = self.db.monitors();
-        db.delete_all()?;
+    fn refresh_monitors(
+        db: &mut DbContext,
+        profile: &Profile,
+        datadog_api: &Api,
+    ) -> Result<(), Error> {
         let results = datadog_api.get_monitors()?;
-        db.insert(&results)?;
+        db.monitors()
+            .insert(&profile.name, &profile.app_host(), &results)?;
+        refresh::report(db.conn(), "monitors", &profile.name, results.len())?;
         Ok(())
     }
 
-    /// Query `DataDog` Time Boards
-    ///
-    /// # Errors
-    /// can return when database error occurs.
-    pub fn query_timeboards<'items>(&mut self, title: &str) -> Result<Vec<Item<'items>>, Error> {
-        let results = self.db.timeboards().find(title, 10)?;
-        let items = results
-            .into_iter()
-            .map(|m| {
-                alfred::ItemBuilder::new(m.title.clone())
-                    .subtitle(m.description)
-                    .autocomplete(m.title)
-                    .arg(format!("open {}", m.url))
-                    .into_item()
-            })
-            .collect();
-        Ok(items)
+    fn find_timeboards(&mut self, title: &str) -> Result<Vec<TimeBoard>, Error> {
+        let query = SearchQuery::parse(title);
+        let results = self.db.timeboards().find(&query)?;
+        let selections = self.db.selection_scores(title)?;
+        Ok(fuzzy::rank(
+            &query,
+            results,
+            10,
+            |board| &board.title,
+            |board| selections.get(&board.url).copied().unwrap_or_default(),
+        ))
     }
 
-    /// Query `DataDog` Screen Boards
-    ///
-    /// # Errors
-    /// can return when database error occurs.
-    pub fn query_screenboards<'items>(&mut self, title: &str) -> Result<Vec<Item<'items>>, Error> {
-        let results = self.db.screenboards().find(title, 10)?;
-        let items = results
-            .into_iter()
-            .map(|m| {
-                alfred::ItemBuilder::new(m.title.clone())
-                    .subtitle(m.description)
-                    .autocomplete(m.title)
-                    .arg(format!("open {}", m.url))
-                    .into_item()
-            })
-            .collect();
-        Ok(items)
+    fn find_screenboards(&mut self, title: &str) -> Result<Vec<ScreenBoard>, Error> {
+        let query = SearchQuery::parse(title);
+        let results = self.db.screenboards().find(&query)?;
+        let selections = self.db.selection_scores(title)?;
+        Ok(fuzzy::rank(
+            &query,
+            results,
+            10,
+            |board| &board.title,
+            |board| selections.get(&board.url).copied().unwrap_or_default(),
+        ))
     }
 
-    /// Query `DataDog` Dashboards
-    ///
-    /// # Errors
-    /// can return when database error occurs.
-    pub fn query_dashboards<'items>(&self, title: &str) -> Result<Vec<Item<'items>>, Error> {
-        let results = self.db.find_dashboard(title, 10)?;
-        let items = results
-            .into_iter()
-            .map(|m| {
-                alfred::ItemBuilder::new(m.title.clone())
-                    .subtitle(m.description)
-                    .autocomplete(m.title)
-                    .arg(format!("open {}", m.url))
-                    .into_item()
-            })
-            .collect();
-        Ok(items)
+    fn find_dashboards(&self, title: &str) -> Result<Vec<Dashboard>, Error> {
+        let query = SearchQuery::parse(title);
+        let results = self.db.find_dashboard(&query)?;
+        let selections = self.db.selection_scores(title)?;
+        Ok(fuzzy::rank(
+            &query,
+            results,
+            10,
+            |board| &board.title,
+            |board| selections.get(&board.url).copied().unwrap_or_default(),
+        ))
     }
 
-    /// Query `DataDog` Monitors
-    ///
-    /// # Errors
-    /// can return when database error occurs.
-    pub fn query_monitors<'items>(
-        &mut self,
-        name: &str,
-        tag: Option<&str>,
-    ) -> Result<Vec<Item<'items>>, Error> {
-        let results = self.db.monitors().find(name, tag, 10)?;
-        let items = results
+    fn find_monitors(&mut self, name: &str, tag: Option<&str>) -> Result<Vec<Monitor>, Error> {
+        let mut query = SearchQuery::parse(name);
+        if let Some(t) = tag {
+            query = query.and("tag", t);
+        }
+        let results = self.db.monitors().find(&query)?;
+        let selections = self.db.selection_scores(name)?;
+        Ok(fuzzy::rank(
+            &query,
+            results,
+            10,
+            |monitor| &monitor.name,
+            |monitor| selections.get(&monitor.url).copied().unwrap_or_default(),
+        ))
+    }
+}
+
+fn timeboard_item(m: TimeBoard) -> Item<'static> {
+    board_item(&m.profile, m.title, &m.description, m.url)
+}
+
+fn screenboard_item(m: ScreenBoard) -> Item<'static> {
+    board_item(&m.profile, m.title, &m.description, m.url)
+}
+
+fn dashboard_item(m: Dashboard) -> Item<'static> {
+    board_item(&m.profile, m.title, &m.description, m.url)
+}
+
+fn board_item(profile: &str, title: String, description: &str, url: String) -> Item<'static> {
+    alfred::ItemBuilder::new(title.clone())
+        .uid(profile::uid(profile, &url))
+        .subtitle(profile::subtitle(profile, description))
+        .autocomplete(title.clone())
+        .arg(format!("open {url}"))
+        .arg_mod(Modifier::Command, format!("open {url}?tv_mode=true"))
+        .subtitle_mod(Modifier::Command, "Open in TV mode")
+        .quicklook_url(url.clone())
+        .text_copy(url)
+        .text_large_type(title)
+        .into_item()
+}
+
+fn monitor_item(m: Monitor) -> Item<'static> {
+    alfred::ItemBuilder::new(m.name.clone())
+        .uid(profile::uid(&m.profile, &m.url))
+        .subtitle(profile::subtitle(&m.profile, &m.name))
+        .autocomplete(m.name.clone())
+        .arg(format!("open {}", m.url))
+        .arg_mod(Modifier::Command, format!("open {}/edit", m.url))
+        .subtitle_mod(Modifier::Command, "Edit monitor")
+        .quicklook_url(m.url.clone())
+        .text_copy(m.url)
+        .text_large_type(m.name)
+        .into_item()
+}
+
+/// Pairs each row with the item showing it.
+fn records<T: Serialize>(rows: Vec<T>, item: fn(T) -> Item<'static>) -> Result<Vec<Record>, Error> {
+    rows.into_iter()
+        .map(|row| Ok(Record::new(row, item)?))
+        .collect()
+}
+
+impl workflow::Workflow for Workflow {
+    type Config = Config;
+    type Error = Error;
+
+    const SERVICE: &'static str = "Datadog";
+    const RESOURCES: &'static [&'static str] = database::RESOURCES;
+    const SEARCH_INDEX: &'static [fts::Source] = database::SEARCH_INDEX;
+    const TRACKED: &'static [changes::Tracked] = database::TRACKED;
+    const STAGED_TABLES: &'static [&'static str] = database::STAGED_TABLES;
+    const MODES: &'static [Mode] = &[
+        Mode::new(
+            MODE_DASHBOARDS,
+            "Search dashboards, timeboards and screenboards together",
+        ),
+        Mode::new(MODE_TIMEBOARDS, "Search timeboards"),
+        Mode::new(MODE_SCREENBOARDS, "Search screenboards"),
+        Mode::new(
+            MODE_MONITORS,
+            "Search monitors, tag:<tag> or --tag <tag> filters by a tag",
+        ),
+    ];
+
+    #[inline]
+    fn allowed_hosts(_config: &Config) -> Vec<String> {
+        ALLOWED_HOSTS.iter().map(|&host| host.to_owned()).collect()
+    }
+
+    #[inline]
+    fn open_command(config: &Config) -> Option<&str> {
+        config.cache.open_command.as_deref()
+    }
+
+    #[inline]
+    fn cache_ttl(config: &Config) -> Duration {
+        config.cache.cache_ttl
+    }
+
+    #[inline]
+    fn stale_after(config: &Config) -> Duration {
+        config.cache.stale_after
+    }
+
+    fn from_config(config: Config) -> Result<Self, Error> {
+        let profiles = config
+            .profiles
             .into_iter()
-            .map(|m| {
-                alfred::ItemBuilder::new(m.name.clone())
-                    .subtitle(m.name.clone())
-                    .autocomplete(m.name)
-                    .arg(format!("open {}", m.url))
-                    .into_item()
+            .map(|profile| Profile {
+                http: profile.api.http(),
+                name: profile.api.name,
+                api_key: profile.api.api_key,
+                application_key: profile.application_key,
+                api_url: profile.api.api_url,
+                subdomain: profile.subdomain,
             })
             .collect();
-        Ok(items)
+        Self::with_profiles(&config.cache.database_url, profiles)
+    }
+
+    #[inline]
+    fn connection(&self) -> &Connection {
+        self.db.conn()
+    }
+
+    #[inline]
+    fn connection_mut(&mut self) -> &mut Connection {
+        self.db.conn_mut()
+    }
+
+    #[inline]
+    fn fetch(&mut self) -> Result<(), Error> {
+        self.refresh_all()
+    }
+
+    /// Searches dashboards unless the query starts with another mode.
+    fn query(&mut self, mode: Option<&str>, query: &str) -> Result<Vec<Record>, Error> {
+        match mode {
+            Some(MODE_TIMEBOARDS) => records(self.find_timeboards(query)?, timeboard_item),
+            Some(MODE_SCREENBOARDS) => records(self.find_screenboards(query)?, screenboard_item),
+            Some(MODE_MONITORS) => {
+                let (query, tag) = take_tag(query);
+                records(self.find_monitors(&query, tag.as_deref())?, monitor_item)
+            }
+            _ => records(self.find_dashboards(query)?, dashboard_item),
+        }
+    }
+}
+
+/// Takes the `--tag <tag>` option out of a monitor query.
+fn take_tag(query: &str) -> (String, Option<String>) {
+    if !query.contains(TAG_OPTION) {
+        return (query.to_owned(), None);
+    }
+    let mut words = Vec::new();
+    let mut tag = None;
+    let mut iter = query.split_whitespace();
+    while let Some(word) = iter.next() {
+        if word == TAG_OPTION {
+            tag = iter.next().map(str::to_owned);
+        } else if let Some(t) = word
+            .strip_prefix(TAG_OPTION)
+            .and_then(|w| w.strip_prefix('='))
+        {
+            tag = Some(t.to_owned());
+        } else {
+            words.push(word);
+        }
     }
+    (words.join(" "), tag)
 }
@@ -0,0 +1,261 @@
+use alfred_workflow::failure::{Failure, ToFailure};
+use alfred_workflow::http::Client;
+use alfred_workflow::mock_server::{self, MockServer, Route};
+use alfred_workflow::search::SearchQuery;
+use alfred_workflow::secret::Secret;
+use alfred_workflow::snapshot::{Format, ImportMode};
+use alfred_workflow::workflow::Workflow as _;
+use chrono::{TimeDelta, Utc};
+use datadog_workflow_lib::workflow::Workflow;
+use std::time::Duration;
+
+const DASH: &str = include_str!("fixtures/dash.json");
+const SCREEN: &str = include_str!("fixtures/screen.json");
+const MONITOR: &str = include_str!("fixtures/monitor.json");
+const MONITOR_CHANGED: &str = include_str!("fixtures/monitor_changed.json");
+const FORBIDDEN: &str = include_str!("fixtures/forbidden.json");
+
+fn serve_boards_and_monitors(server: &MockServer) {
+    server.route(Route::get("/v1/dash").json(DASH));
+    server.route(Route::get("/v1/screen").json(SCREEN));
+    server.route(Route::get("/v1/monitor").json(MONITOR));
+}
+
+fn workflow(api_url: &str, database_url: &str) -> Workflow {
+    let http = Client::builder().backoff(Duration::from_millis(10));
+    Workflow::new(
+        Secret::new("key"),
+        Secret::new("app-key"),
+        database_url,
+        api_url,
+        "acme",
+        http,
+    )
+    .unwrap()
+}
+
+fn titles(wf: &mut Workflow, mode: &str, query: &str) -> Vec<String> {
+    wf.query(Some(mode), query)
+        .unwrap()
+        .into_iter()
+        .map(|record| record.item.title.into_owned())
+        .collect()
+}
+
+#[test]
+fn refresh_then_query_finds_boards_and_monitors() {
+    let server = MockServer::start();
+    serve_boards_and_monitors(&server);
+    let url = server.url();
+    let db = mock_server::temp_database("datadog-refresh");
+    let mut wf = workflow(&url, db.to_str().unwrap());
+
+    wf.refresh().unwrap();
+
+    assert_eq!(
+        titles(&mut wf, "d", ""),
+        ["Payments Overview", "NOC Wall", "Kubernetes Nodes"]
+    );
+    assert_eq!(titles(&mut wf, "t", "kube"), ["Kubernetes Nodes"]);
+    let boards = wf.query(Some("s"), "noc").unwrap();
+    assert_eq!(
+        boards[0].item.arg.as_deref(),
+        Some("open https://acme.datadoghq.com/screen/4521")
+    );
+    assert_eq!(
+        titles(&mut wf, "m", "--tag team:checkout"),
+        ["Payments API p99 latency is high"]
+    );
+    let records = wf.query(Some("m"), "--tag team:checkout").unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].item.title, "Payments API p99 latency is high");
+    assert_eq!(records[0].row["id"], 17001);
+    assert_eq!(
+        records[0].row["url"],
+        "https://acme.datadoghq.com/monitors/17001"
+    );
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 3);
+    assert!(requests[0].target.contains("application_key=app-key"));
+    assert!(requests[0].target.contains("api_key=key"));
+}
+
+#[test]
+fn query_matches_descriptions_and_tags() {
+    let server = MockServer::start();
+    serve_boards_and_monitors(&server);
+    let url = server.url();
+    let db = mock_server::temp_database("datadog-refresh-index");
+    let mut wf = workflow(&url, db.to_str().unwrap());
+
+    wf.refresh().unwrap();
+
+    assert_eq!(
+        titles(&mut wf, "d", "checkout latency"),
+        ["Payments Overview"]
+    );
+    assert_eq!(
+        titles(&mut wf, "m", "checkout"),
+        ["Payments API p99 latency is high"]
+    );
+    assert_eq!(titles(&mut wf, "m", "platform -disk"), Vec::<String>::new());
+}
+
+#[test]
+fn refresh_replaces_monitor_tags() {
+    let server = MockServer::start();
+    serve_boards_and_monitors(&server);
+    let url = server.url();
+    let db = mock_server::temp_database("datadog-refresh-twice");
+    let mut wf = workflow(&url, db.to_str().unwrap());
+
+    wf.refresh().unwrap();
+    wf.refresh().unwrap();
+
+    assert_eq!(
+        titles(&mut wf, "m", "--tag team:platform"),
+        ["Disk usage above 90%"]
+    );
+}
+
+#[test]
+fn refresh_summarizes_changes_and_lists_new_monitors() {
+    let server = MockServer::start();
+    serve_boards_and_monitors(&server);
+    let url = server.url();
+    let db = mock_server::temp_database("datadog-refresh-changes");
+    let mut wf = workflow(&url, db.to_str().unwrap());
+
+    let summary = wf.refresh().unwrap();
+    assert_eq!(
+        summary.to_string(),
+        "+2 timeboards, +1 screenboard, +2 monitors"
+    );
+    let since = Utc::now() - TimeDelta::days(7);
+    // the first refresh did not see anything appear
+    assert!(
+        wf.first_seen_since(since, &SearchQuery::default())
+            .unwrap()
+            .is_empty()
+    );
+
+    let changed = MockServer::start();
+    changed.route(Route::get("/v1/dash").json(DASH));
+    changed.route(Route::get("/v1/screen").json(SCREEN));
+    changed.route(Route::get("/v1/monitor").json(MONITOR_CHANGED));
+    let url = changed.url();
+    let mut wf = workflow(&url, db.to_str().unwrap());
+
+    let summary = wf.refresh().unwrap();
+    assert_eq!(
+        summary.to_string(),
+        "+1 monitor, −1 monitor, 1 monitor renamed"
+    );
+    let new = wf.first_seen_since(since, &SearchQuery::default()).unwrap();
+    assert_eq!(new.len(), 1);
+    assert_eq!(new[0].noun, "monitor");
+    assert_eq!(new[0].title, "Checkout error rate is high");
+    assert_eq!(new[0].url, "https://acme.datadoghq.com/monitors/17003");
+    assert!(
+        wf.first_seen_since(since, &SearchQuery::parse("payments"))
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn failed_refresh_keeps_the_cached_boards() {
+    let server = MockServer::start();
+    serve_boards_and_monitors(&server);
+    let url = server.url();
+    let db = mock_server::temp_database("datadog-refresh-failed");
+    workflow(&url, db.to_str().unwrap()).refresh().unwrap();
+
+    // monitors fail after the boards were fetched, so nothing is swapped in
+    let failing = MockServer::start();
+    failing.route(Route::get("/v1/dash").json(r#"{"dashes": []}"#));
+    failing.route(Route::get("/v1/screen").json(r#"{"screenboards": []}"#));
+    failing.route(Route::get("/v1/monitor").status(403).json(FORBIDDEN));
+    let url = failing.url();
+    let mut wf = workflow(&url, db.to_str().unwrap());
+
+    let err = wf.refresh().unwrap_err();
+    assert_eq!(
+        err.to_failure(),
+        Failure::Unauthorized {
+            service: "Datadog",
+            status: 403
+        }
+    );
+    assert_eq!(titles(&mut wf, "d", "noc"), ["NOC Wall"]);
+}
+
+#[test]
+fn exported_snapshot_imports_into_an_empty_cache() {
+    let server = MockServer::start();
+    serve_boards_and_monitors(&server);
+    let url = server.url();
+    let db = mock_server::temp_database("datadog-export");
+    let mut wf = workflow(&url, db.to_str().unwrap());
+    wf.refresh().unwrap();
+
+    for format in [Format::Json, Format::Ndjson] {
+        let mut snapshot = Vec::new();
+        let exported = wf.export(format, &mut snapshot).unwrap();
+
+        // without ever contacting the API
+        let db = mock_server::temp_database(&format!("datadog-import-{format}"));
+        let mut imported = workflow("http://127.0.0.1:9", db.to_str().unwrap());
+        assert_eq!(
+            imported
+                .import(format, ImportMode::Merge, &snapshot[..])
+                .unwrap(),
+            exported
+        );
+        // merging the same snapshot again adds nothing, even to monitor tags without a key
+        imported
+            .import(format, ImportMode::Merge, &snapshot[..])
+            .unwrap();
+        imported
+            .import(format, ImportMode::Replace, &snapshot[..])
+            .unwrap();
+
+        assert_eq!(
+            titles(&mut imported, "d", ""),
+            ["Payments Overview", "NOC Wall", "Kubernetes Nodes"]
+        );
+        let tags: i64 = imported
+            .connection()
+            .query_row("SELECT COUNT(*) FROM monitor_tags", [], |row| row.get(0))
+            .unwrap();
+        let original: i64 = wf
+            .connection()
+            .query_row("SELECT COUNT(*) FROM monitor_tags", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(tags, original);
+        assert_eq!(
+            titles(&mut imported, "m", "checkout"),
+            ["Payments API p99 latency is high"]
+        );
+        assert_eq!(
+            imported.status().unwrap()[0].refreshed_at,
+            wf.status().unwrap()[0].refreshed_at
+        );
+    }
+}
+
+#[test]
+fn snapshot_of_another_workflow_is_rejected() {
+    let db = mock_server::temp_database("datadog-import-other");
+    let mut wf = workflow("http://127.0.0.1:9", db.to_str().unwrap());
+    let snapshot = r#"{"version": 1, "workflow": "github", "schema_version": 3, "tables": {}}"#;
+
+    let err = wf
+        .import(Format::Json, ImportMode::Replace, snapshot.as_bytes())
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "snapshot of the github workflow cannot be imported into the datadog workflow"
+    );
+}
@@ -8,4 +8,7 @@ pub enum Error {
 
     #[error("failed to write alfred items->json {}", _0)]
     WriteItems(#[from] io::Error),
+
+    #[error(transparent)]
+    AlfredWorkflow(#[from] alfred_workflow::errors::Error),
 }
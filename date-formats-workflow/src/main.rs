@@ -1,6 +1,7 @@
 mod errors;
 
-use alfred::{json, Item};
+use alfred::Item;
+use alfred_workflow::response::ScriptFilterResponse;
 use anyhow::Error as AnyError;
 use chrono::prelude::*;
 use chrono::{Local, Utc};
@@ -8,7 +9,6 @@ use chrono_tz::Tz;
 use clap::Parser;
 use errors::Error;
 use std::io;
-use std::io::Write;
 use std::str::FromStr;
 
 #[derive(Parser)]
@@ -52,7 +52,7 @@ fn main() -> Result<(), AnyError> {
             .autocomplete(format!(" {NOW_DT}"))
             .arg(format!(" {} --tz {NOW_DT}", &tz))
             .into_item();
-        write_items(io::stdout(), &[now])?;
+        write_items(vec![now])?;
     }
     Ok(())
 }
@@ -71,34 +71,41 @@ fn parse_timezone_and_date(ndt: &DateTime<Utc>, tz: &str) -> Result<DateTime<Tz>
 }
 
 #[inline]
-fn write_items<W>(writer: W, items: &[Item]) -> Result<(), Error>
-where
-    W: Write,
-{
-    Ok(json::write_items(writer, items)?)
+fn write_items(items: Vec<Item>) -> Result<(), Error> {
+    Ok(ScriptFilterResponse::new()
+        .items(items)
+        .write(io::stdout())?)
 }
 
 #[inline]
 fn write_variations(dt: &DateTime<Tz>) -> Result<(), Error> {
-    let unix_sec = build_item(dt.timestamp().to_string(), "UNIX timestamp - seconds");
+    let unix_sec = build_item(
+        "unix-seconds",
+        dt.timestamp().to_string(),
+        "UNIX timestamp - seconds",
+    );
     let unix_milli = build_item(
+        "unix-milliseconds",
         dt.timestamp_millis().to_string(),
         "UNIX timestamp - milliseconds",
     );
     let unix_nano = build_item(
+        "unix-nanoseconds",
         dt.timestamp_nanos_opt().unwrap_or_default().to_string(),
         "UNIX timestamp - nanoseconds",
     );
     let rfc_3339 = build_item(
+        "rfc-3339",
         dt.to_rfc3339_opts(SecondsFormat::Secs, true),
         "rfc_3339 - iso8601 compatible",
     );
     let rfc_3339_nano = build_item(
+        "rfc-3339-nano",
         dt.to_rfc3339_opts(SecondsFormat::Nanos, true),
         "rfc_3339_nano - iso8601 compatible",
     );
-    let rfc_2822 = build_item(dt.to_rfc2822(), "rfc_2822");
-    let alt = build_item(dt.format("%e %b %Y %H:%M:%S").to_string(), "");
+    let rfc_2822 = build_item("rfc-2822", dt.to_rfc2822(), "rfc_2822");
+    let alt = build_item("human", dt.format("%e %b %Y %H:%M:%S").to_string(), "");
 
     let diff = dt.with_timezone(&Utc).signed_duration_since(Utc::now());
     let attr = if diff.num_nanoseconds().unwrap() < 0 {
@@ -119,35 +126,36 @@ fn write_variations(dt: &DateTime<Tz>) -> Result<(), Error> {
         diff.num_seconds().abs() % 60,
         attr
     );
-    let time_since = build_item(diff_str, decor);
+    let time_since = build_item("time-since", diff_str, decor);
 
     let time_current_tz = build_item(
+        "local-timezone",
         dt.with_timezone(&Local)
             .format("%e %b %Y %H:%M:%S")
             .to_string(),
         "Time in local timezone",
     );
 
-    write_items(
-        io::stdout(),
-        &[
-            unix_sec,
-            unix_milli,
-            unix_nano,
-            alt,
-            time_current_tz,
-            rfc_2822,
-            rfc_3339,
-            rfc_3339_nano,
-            time_since,
-        ],
-    )
+    write_items(vec![
+        unix_sec,
+        unix_milli,
+        unix_nano,
+        alt,
+        time_current_tz,
+        rfc_2822,
+        rfc_3339,
+        rfc_3339_nano,
+        time_since,
+    ])
 }
 
 #[inline]
-fn build_item(date_string: String, subtitle: &str) -> Item {
+fn build_item<'a>(uid: &'a str, date_string: String, subtitle: &'a str) -> Item<'a> {
     alfred::ItemBuilder::new(date_string.clone())
+        .uid(uid)
         .subtitle(subtitle)
-        .arg(date_string)
+        .arg(date_string.clone())
+        .text_copy(date_string.clone())
+        .text_large_type(date_string)
         .into_item()
 }
@@ -2,8 +2,81 @@ pub mod errors;
 pub mod models;
 
 use crate::database::models::Repository;
+use alfred_workflow::migrations::Migration;
+use alfred_workflow::search::{SearchFields, SearchQuery};
+use alfred_workflow::{changes, failure, frecency, fts, refresh};
 use errors::Error;
+use log::debug;
 use rusqlite::{Connection, ToSql};
+use std::collections::HashMap;
+
+const MIGRATIONS: &[Migration] = &[
+    Migration::new(
+        1,
+        "create repositories table",
+        "CREATE TABLE IF NOT EXISTS repositories (
+            name_with_owner TEXT     NOT NULL PRIMARY KEY,
+            name            TEXT     NOT NULL,
+            url             TEXT     NOT NULL,
+            pushed_at       DATETIME NOT NULL
+        );",
+    ),
+    Migration::new(2, "create selections table", frecency::SCHEMA),
+    Migration::new(3, "create refreshes table", refresh::SCHEMA),
+    Migration::new(4, "add refresh duration and error", refresh::OUTCOME_SCHEMA),
+    Migration::new(5, "create search index", fts::SCHEMA),
+    Migration::new(
+        6,
+        "key repositories by profile and node id",
+        // the cached repositories have no id, so they are fetched again by the next refresh
+        "CREATE TABLE repositories_ids (
+            profile         TEXT     NOT NULL DEFAULT '',
+            id              TEXT     NOT NULL,
+            name_with_owner TEXT     NOT NULL,
+            name            TEXT     NOT NULL,
+            url             TEXT     NOT NULL,
+            pushed_at       DATETIME NOT NULL,
+            first_seen      INTEGER,
+            PRIMARY KEY (profile, id)
+        );
+        DROP TABLE repositories;
+        ALTER TABLE repositories_ids RENAME TO repositories;
+        DELETE FROM refreshes WHERE resource = 'repositories';",
+    ),
+    Migration::new(7, "create refresh progress table", refresh::PROGRESS_SCHEMA),
+];
+
+/// The cached resources, as tracked in the refreshes table and staged while refreshing.
+pub const RESOURCES: &[&str] = &["repositories"];
+
+/// The resources whose changes each refresh summarizes, repositories keyed by their node id so
+/// a renamed one is renamed rather than removed and added.
+pub const TRACKED: &[changes::Tracked] = &[changes::Tracked {
+    resource: "repositories",
+    noun: "repo",
+    title: "name_with_owner",
+    profile: Some("profile"),
+}];
+
+/// The resources indexed for full-text search, keyed by rowid as a repository may be cached for
+/// several profiles.
+pub const SEARCH_INDEX: &[fts::Source] = &[fts::Source {
+    resource: "repositories",
+    select: "SELECT rowid AS key, url, name_with_owner AS title, '' AS description, '' AS tags
+        FROM repositories",
+}];
+
+const SEARCH_FIELDS: SearchFields = SearchFields {
+    default: &["name"],
+    qualifiers: &[
+        ("repo", "name"),
+        (
+            "owner",
+            "substr(name_with_owner, 1, instr(name_with_owner, '/') - 1)",
+        ),
+    ],
+    profile: Some("profile"),
+};
 
 pub struct DbContext {
     conn: Connection,
@@ -12,65 +85,63 @@ pub struct DbContext {
 impl DbContext {
     #[inline]
     pub fn new(database_url: &str) -> Result<Self, Error> {
-        let conn = Connection::open(database_url)?;
+        let conn = alfred_workflow::open_database_at(database_url, MIGRATIONS)?;
         Ok(DbContext { conn })
     }
 
     #[inline]
-    pub fn run_migrations(&self) -> Result<(), Error> {
-        self.conn.execute_batch(
-            "CREATE TABLE IF NOT EXISTS repositories (
-                    name_with_owner TEXT     NOT NULL PRIMARY KEY,
-                    name            TEXT     NOT NULL,
-                    url             TEXT     NOT NULL,
-                    pushed_at       DATETIME NOT NULL
-                );",
-        )?;
-        Ok(())
-    }
-
-    #[inline]
-    pub fn delete_repositories(&self) -> Result<(), Error> {
-        self.conn.execute_batch("DELETE FROM repositories;")?;
-        Ok(())
-    }
-
-    #[inline]
-    pub fn find_repositories(&self, repo_name: &str, limit: i64) -> Result<Vec<Repository>, Error> {
-        // This will allow searching by full name or just the words within the name;
-        // it's not a regex but it's good enough.
-        let name = format!(
-            "%{}%",
-            repo_name
-                .split_terminator(' ')
-                .flat_map(|s| s.split_terminator('_'))
-                .flat_map(|s| s.split_terminator('-'))
-                .collect::<Vec<&str>>()
-                .join("%")
+    pub fn find_repositories(&self, query: &SearchQuery) -> Result<Vec<Repository>, Error> {
+        let search = fts::compile(
+            query,
+            &["repositories"],
+            "fts.key = repositories.rowid",
+            &SEARCH_FIELDS,
         );
+        let params = search
+            .params
+            .iter()
+            .map(|p| p as &dyn ToSql)
+            .collect::<Vec<_>>();
 
-        self.conn.prepare(
-            "SELECT name_with_owner, name, url, pushed_at FROM repositories WHERE name LIKE ? ORDER BY pushed_at DESC LIMIT ?",
-        )?.query_map([&name as &dyn ToSql,&limit], |row| {
+        let repositories = self.conn.prepare(&format!(
+            "SELECT profile, id, name_with_owner, name, url, pushed_at FROM repositories {} WHERE {} ORDER BY {} LIMIT {}",
+            search.join,
+            search.clause,
+            search.order_by("pushed_at DESC"),
+            fts::CANDIDATES
+        ))?.query_map(&*params, |row| {
             Ok(Repository{
-                name_with_owner: row.get(0)?,
-                name:row.get(1)?,
-                url:row.get(2)?,
-                pushed_at:row.get(3)?,
+                profile: row.get(0)?,
+                id: row.get(1)?,
+                name_with_owner: row.get(2)?,
+                name:row.get(3)?,
+                url:row.get(4)?,
+                pushed_at:row.get(5)?,
             })
         })?.map(|r|{
             Ok(r?)
-        }).collect::<Result<Vec<_>, _>>()
+        }).collect::<Result<Vec<_>, Error>>()?;
+
+        if repositories.is_empty() {
+            failure::ensure_cached(&self.conn, &["repositories"])?;
+        }
+        Ok(repositories)
     }
 
     #[inline]
-    pub fn insert_repositories(&mut self, repositories: &[Repository]) -> Result<(), Error> {
+    pub fn insert_repositories(
+        &mut self,
+        profile: &str,
+        repositories: &[Repository],
+    ) -> Result<(), Error> {
         let tx = self.conn.transaction()?;
-        let mut stmt = tx.prepare("INSERT INTO repositories (name_with_owner, name, url, pushed_at) VALUES (?1, ?2, ?3, ?4)")?;
+        let mut stmt = tx.prepare("INSERT INTO repositories_staging (profile, id, name_with_owner, name, url, pushed_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)")?;
 
         for repo in repositories {
             stmt.execute([
-                &repo.name_with_owner as &dyn ToSql,
+                &profile as &dyn ToSql,
+                &repo.id,
+                &repo.name_with_owner,
                 &repo.name,
                 &repo.url,
                 &repo.pushed_at,
@@ -79,14 +150,23 @@ impl DbContext {
 
         stmt.finalize()?;
         tx.commit()?;
+        debug!("staged {} repositories", repositories.len());
 
         Ok(())
     }
 
     #[inline]
-    pub fn optimize(&self) -> Result<(), Error> {
-        // since this workflow is READ heavy, let's optimize the SQLite indexes and DB
-        self.conn.execute("VACUUM;", [])?;
-        Ok(())
+    pub fn selection_scores(&self, query: &str) -> Result<HashMap<String, i64>, Error> {
+        Ok(frecency::scores(&self.conn, query)?)
+    }
+
+    #[inline]
+    pub const fn conn(&self) -> &Connection {
+        &self.conn
+    }
+
+    #[inline]
+    pub const fn conn_mut(&mut self) -> &mut Connection {
+        &mut self.conn
     }
 }
@@ -1,7 +1,11 @@
 use chrono::{DateTime, Utc};
+use serde::Serialize;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct Repository {
+    pub profile: String,
+    /// The repository's node id, which stays the same when it is renamed or transferred.
+    pub id: String,
     pub name_with_owner: String,
     pub name: String,
     pub url: String,
@@ -1,21 +1,25 @@
 use crate::database::models::Repository;
 use crate::errors::Error;
+use alfred_workflow::http::Client;
 use chrono::{DateTime, Utc};
-use reqwest::header::{CONTENT_TYPE, USER_AGENT};
+use log::debug;
+use reqwest::header::CONTENT_TYPE;
 
 #[derive(Debug)]
 pub struct GitHubAPI<'a> {
+    client: &'a Client,
+    url: &'a str,
     token: &'a str,
 }
 
 impl<'a> GitHubAPI<'a> {
     #[inline]
-    pub const fn new(token: &'a str) -> Self {
-        Self { token }
+    pub const fn new(client: &'a Client, url: &'a str, token: &'a str) -> Self {
+        Self { client, url, token }
     }
 
     #[inline]
-    pub const fn accessible_repositories(&self) -> OwnedRepositories {
+    pub const fn accessible_repositories(&self) -> OwnedRepositories<'_> {
         OwnedRepositories {
             api: self,
             has_more: true,
@@ -36,6 +40,7 @@ impl<'a> GitHubAPI<'a> {
                         }} \
                         edges {{ \
                             node {{ \
+                                id \
                                 pushedAt \
                                 url \
                             }} \
@@ -54,14 +59,13 @@ impl<'a> GitHubAPI<'a> {
         q.push_str(&escaped);
         q.push_str("\" }");
 
-        let results: Results = reqwest::blocking::Client::new()
-            .post("https://api.github.com/graphql")
+        let request = self
+            .client
+            .post(&format!("{}/graphql", self.url))
             .bearer_auth(self.token)
             .header(CONTENT_TYPE, "application/json")
-            .header(USER_AGENT, "Alfred Github Workflow")
-            .body(q)
-            .send()?
-            .json()?;
+            .body(q);
+        let results: Results = self.client.send(request)?.error_for_status()?.json()?;
 
         Ok(results)
     }
@@ -74,44 +78,56 @@ pub struct OwnedRepositories<'a> {
 }
 
 impl Iterator for OwnedRepositories<'_> {
-    type Item = Vec<Repository>;
+    type Item = Result<Vec<Repository>, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
         if !self.has_more {
             return None;
         }
-        let results = self
-            .api
-            .fetch_repositories(self.cursor.take())
-            .expect("unable to fetch data from the GitHub API");
+        let results = match self.api.fetch_repositories(self.cursor.take()) {
+            Ok(results) => results,
+            Err(e) => {
+                self.has_more = false;
+                return Some(Err(e));
+            }
+        };
         self.has_more = results.data.viewer.repositories.page_info.has_next_page;
+        debug!(
+            "fetched {} repositories, {}",
+            results.data.viewer.repositories.edges.len(),
+            if self.has_more {
+                "more to come"
+            } else {
+                "last page"
+            }
+        );
         if self.has_more {
             self.cursor = Some(results.data.viewer.repositories.page_info.end_cursor);
         }
-        Some(
-            results
-                .data
-                .viewer
-                .repositories
-                .edges
-                .into_iter()
-                .filter_map(|edge| match edge {
-                    Some(e) => e.node,
-                    _ => None,
-                })
-                .map(|node| {
-                    let mut s = node.url.rsplit('/');
-                    let name = s.next().unwrap_or_default().to_string();
-                    let owner = s.next().unwrap_or_default();
-                    Repository {
-                        name_with_owner: format!("{owner}/{name}"),
-                        name,
-                        url: node.url,
-                        pushed_at: node.pushed_at,
-                    }
-                })
-                .collect(),
-        )
+        Some(Ok(results
+            .data
+            .viewer
+            .repositories
+            .edges
+            .into_iter()
+            .filter_map(|edge| match edge {
+                Some(e) => e.node,
+                _ => None,
+            })
+            .map(|node| {
+                let mut s = node.url.rsplit('/');
+                let name = s.next().unwrap_or_default().to_string();
+                let owner = s.next().unwrap_or_default();
+                Repository {
+                    profile: String::new(),
+                    id: node.id,
+                    name_with_owner: format!("{owner}/{name}"),
+                    name,
+                    url: node.url,
+                    pushed_at: node.pushed_at,
+                }
+            })
+            .collect()))
     }
 }
 
@@ -125,6 +141,7 @@ struct PageInfo {
 
 #[derive(Debug, Deserialize)]
 struct Node {
+    id: String,
     url: String,
     #[serde(rename = "pushedAt")]
     pushed_at: DateTime<Utc>,
@@ -1,59 +1,198 @@
-use crate::database::DbContext;
+use crate::config::Config;
+use crate::database::models::Repository;
+use crate::database::{self, DbContext};
 use crate::errors::Error;
 use crate::github::GitHubAPI;
-use alfred::Item;
+use alfred::{Item, Modifier};
+use alfred_workflow::http::ClientBuilder;
+use alfred_workflow::output::Record;
+use alfred_workflow::search::SearchQuery;
+use alfred_workflow::secret::Secret;
+use alfred_workflow::workflow;
+use alfred_workflow::{changes, fts, fuzzy, opener, profile, refresh};
+use log::info;
+use rusqlite::Connection;
+use std::time::Duration;
 
-pub struct Workflow<'a> {
-    api_key: &'a str,
+/// Hosts GitHub.com results link to, GitHub Enterprise Server ones link to the host of the
+/// profile's API.
+pub const ALLOWED_HOSTS: &[&str] = &["github.com"];
+
+/// Sent with every request, as the GitHub API requires.
+const USER_AGENT: &str = "Alfred Github Workflow";
+
+/// An account the cache is refreshed from, whose name its rows are cached under.
+pub struct Profile {
+    pub name: String,
+    pub api_key: Secret,
+    pub api_url: String,
+    pub http: ClientBuilder,
+}
+
+pub struct Workflow {
+    profiles: Vec<Profile>,
     db: DbContext,
 }
 
-impl<'a> Workflow<'a> {
+impl Workflow {
+    /// Creates the workflow with a single, unnamed, profile.
+    ///
     /// # Errors
     ///
     /// Will return `Err` if `database` could not be connected to.
     ///
     #[inline]
-    pub fn new(api_key: &'a str, database_url: &str) -> Result<Self, Error> {
-        let db = DbContext::new(database_url)?;
-        Ok(Workflow { api_key, db })
+    pub fn new(
+        api_key: Secret,
+        api_url: &str,
+        database_url: &str,
+        http: ClientBuilder,
+    ) -> Result<Self, Error> {
+        let profile = Profile {
+            name: String::new(),
+            api_key,
+            api_url: api_url.to_owned(),
+            http,
+        };
+        Self::with_profiles(database_url, vec![profile])
     }
 
+    /// Creates the workflow refreshing the cache from every profile.
+    ///
     /// # Errors
     ///
-    /// Will return `Err` if Contacting GitHub returns an error.
+    /// Will return `Err` if `database` could not be connected to.
     ///
     #[inline]
-    pub fn refresh_cache(&mut self) -> Result<(), Error> {
-        self.db.run_migrations()?;
-        let api = GitHubAPI::new(self.api_key);
+    pub fn with_profiles(database_url: &str, profiles: Vec<Profile>) -> Result<Self, Error> {
+        let db = DbContext::new(database_url)?;
+        Ok(Workflow { profiles, db })
+    }
+
+    /// Returns the repositories best matching the query, best first.
+    fn find(&self, repo_name: &str) -> Result<Vec<Repository>, Error> {
+        let query = SearchQuery::parse(repo_name);
+        let repositories = self.db.find_repositories(&query)?;
+        let selections = self.db.selection_scores(repo_name)?;
+        Ok(fuzzy::rank(
+            &query,
+            repositories,
+            10,
+            |repo| &repo.name,
+            |repo| selections.get(&repo.url).copied().unwrap_or_default(),
+        ))
+    }
+}
+
+fn item(repo: Repository) -> Item<'static> {
+    alfred::ItemBuilder::new(repo.name_with_owner.clone())
+        .uid(profile::uid(&repo.profile, &repo.name_with_owner))
+        .subtitle(profile::subtitle(&repo.profile, &repo.name))
+        .autocomplete(repo.name)
+        .arg(format!("open {}", repo.url))
+        .arg_mod(Modifier::Command, format!("open {}/pulls", repo.url))
+        .subtitle_mod(Modifier::Command, "Open pull requests")
+        .arg_mod(Modifier::Option, format!("open {}/actions", repo.url))
+        .subtitle_mod(Modifier::Option, "Open actions")
+        .arg_mod(Modifier::Control, format!("open {}/issues", repo.url))
+        .subtitle_mod(Modifier::Control, "Open issues")
+        .quicklook_url(repo.url.clone())
+        .text_copy(repo.url)
+        .text_large_type(repo.name_with_owner)
+        .into_item()
+}
 
-        self.db.delete_repositories()?;
+impl workflow::Workflow for Workflow {
+    type Config = Config;
+    type Error = Error;
 
-        for v in api.accessible_repositories() {
-            self.db.insert_repositories(&v)?;
-        }
-        // and DB cleanup work
-        self.db.optimize()?;
-        Ok(())
+    const SERVICE: &'static str = "GitHub";
+    const RESOURCES: &'static [&'static str] = database::RESOURCES;
+    const SEARCH_INDEX: &'static [fts::Source] = database::SEARCH_INDEX;
+    const TRACKED: &'static [changes::Tracked] = database::TRACKED;
+
+    fn allowed_hosts(config: &Config) -> Vec<String> {
+        let mut hosts = ALLOWED_HOSTS
+            .iter()
+            .map(|&host| host.to_owned())
+            .chain(
+                config
+                    .profiles
+                    .iter()
+                    .filter_map(|profile| opener::site_host(&profile.api_url)),
+            )
+            .collect::<Vec<_>>();
+        hosts.sort_unstable();
+        hosts.dedup();
+        hosts
     }
 
-    /// # Errors
-    ///
-    /// Will return `Err` if querying the database fails.
-    ///
     #[inline]
-    pub fn query<'items>(&self, repo_name: &str) -> Result<Vec<Item<'items>>, Error> {
-        self.db
-            .find_repositories(repo_name, 10)?
+    fn open_command(config: &Config) -> Option<&str> {
+        config.cache.open_command.as_deref()
+    }
+
+    #[inline]
+    fn cache_ttl(config: &Config) -> Duration {
+        config.cache.cache_ttl
+    }
+
+    #[inline]
+    fn stale_after(config: &Config) -> Duration {
+        config.cache.stale_after
+    }
+
+    fn from_config(config: Config) -> Result<Self, Error> {
+        let profiles = config
+            .profiles
             .into_iter()
-            .map(|repo| {
-                Ok(alfred::ItemBuilder::new(repo.name_with_owner)
-                    .subtitle(repo.name.clone())
-                    .autocomplete(repo.name)
-                    .arg(format!("open {}", repo.url))
-                    .into_item())
+            .map(|profile| Profile {
+                http: profile.http(),
+                name: profile.name,
+                api_key: profile.api_key,
+                api_url: profile.api_url,
             })
-            .collect::<Result<Vec<_>, _>>()
+            .collect();
+        Self::with_profiles(&config.cache.database_url, profiles)
+    }
+
+    #[inline]
+    fn connection(&self) -> &Connection {
+        self.db.conn()
+    }
+
+    #[inline]
+    fn connection_mut(&mut self) -> &mut Connection {
+        self.db.conn_mut()
+    }
+
+    fn fetch(&mut self) -> Result<(), Error> {
+        for profile in &self.profiles {
+            if !profile.name.is_empty() {
+                info!("fetching the {} profile", profile.name);
+            }
+            let client = profile.http.clone().user_agent(USER_AGENT).build()?;
+            let api_key = profile.api_key.expose()?;
+            let api = GitHubAPI::new(&client, &profile.api_url, &api_key);
+
+            for v in api.accessible_repositories() {
+                let repositories = v?;
+                self.db.insert_repositories(&profile.name, &repositories)?;
+                refresh::report(
+                    self.db.conn(),
+                    "repositories",
+                    &profile.name,
+                    repositories.len(),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn query(&mut self, _mode: Option<&str>, query: &str) -> Result<Vec<Record>, Error> {
+        self.find(query)?
+            .into_iter()
+            .map(|repo| Ok(Record::new(repo, item)?))
+            .collect()
     }
 }
@@ -0,0 +1,110 @@
+use alfred_workflow::fts;
+use alfred_workflow::http::Client;
+use alfred_workflow::mock_server;
+use alfred_workflow::secret::Secret;
+use alfred_workflow::workflow::Workflow as _;
+use github_workflow_lib::workflow::Workflow;
+use std::time::{Duration, Instant};
+
+/// How many repositories the cache holds, about what a large organization has access to.
+const REPOSITORIES: usize = 50_000;
+
+/// The slowest a search may be, typing shows results at every keystroke; unoptimized builds
+/// are given more.
+const BUDGET: Duration = if cfg!(debug_assertions) {
+    Duration::from_millis(30)
+} else {
+    Duration::from_millis(10)
+};
+
+const OWNERS: &[&str] = &["acme", "globex", "initech", "umbrella", "hooli"];
+const WORDS: &[&str] = &[
+    "payments",
+    "api",
+    "gateway",
+    "service",
+    "ledger",
+    "billing",
+    "auth",
+    "search",
+    "web",
+    "mobile",
+    "infra",
+    "deploy",
+    "docs",
+    "client",
+    "worker",
+    "events",
+    "data",
+    "pipeline",
+    "admin",
+    "portal",
+    "checkout",
+    "cart",
+    "orders",
+    "inventory",
+    "shipping",
+    "metrics",
+];
+
+/// Fills the cache with repositories named after every combination of the words.
+fn populate(wf: &Workflow) {
+    let conn = wf.connection();
+    conn.execute_batch("BEGIN").unwrap();
+    let mut stmt = conn
+        .prepare(
+            "INSERT INTO repositories (id, name_with_owner, name, url, pushed_at)
+             VALUES (?1, ?2, ?3, ?4, '2024-01-01T00:00:00Z')",
+        )
+        .unwrap();
+    for i in 0..REPOSITORIES {
+        let owner = OWNERS[i % OWNERS.len()];
+        let first = WORDS[i / OWNERS.len() % WORDS.len()];
+        let second = WORDS[i / OWNERS.len() / WORDS.len() % WORDS.len()];
+        let name = format!("{first}-{second}-{i}");
+        stmt.execute([
+            format!("R_{i}"),
+            format!("{owner}/{name}"),
+            name.clone(),
+            format!("https://github.com/{owner}/{name}"),
+        ])
+        .unwrap();
+    }
+    conn.execute_batch("COMMIT").unwrap();
+    fts::rebuild(conn, Workflow::SEARCH_INDEX).unwrap();
+}
+
+#[test]
+fn searching_a_large_cache_stays_within_budget() {
+    let db = mock_server::temp_database("github-latency");
+    let mut wf = Workflow::new(
+        Secret::new("token"),
+        "https://api.github.com",
+        db.to_str().unwrap(),
+        Client::builder(),
+    )
+    .unwrap();
+    populate(&wf);
+
+    for query in [
+        "",
+        "a",
+        "p",
+        "pay",
+        "payments api",
+        "pymts",
+        "zzz",
+        "owner:acme gate",
+    ] {
+        // the first search warms the page cache, as earlier keystrokes would have
+        wf.query(None, query).unwrap();
+        let start = Instant::now();
+        let records = wf.query(None, query).unwrap();
+        let elapsed = start.elapsed();
+        println!("{query:?}: {} results in {elapsed:?}", records.len());
+        assert!(
+            elapsed < BUDGET,
+            "searching {query:?} took {elapsed:?}, more than {BUDGET:?}"
+        );
+    }
+}
@@ -0,0 +1,253 @@
+use alfred_workflow::config::{ApiConfig, CacheConfig};
+use alfred_workflow::failure::{Failure, ToFailure};
+use alfred_workflow::http::Client;
+use alfred_workflow::mock_server::{self, MockServer, Route};
+use alfred_workflow::search::SearchQuery;
+use alfred_workflow::secret::Secret;
+use alfred_workflow::workflow::Workflow as _;
+use chrono::{TimeDelta, Utc};
+use github_workflow_lib::config::Config;
+use github_workflow_lib::workflow::{Profile, Workflow};
+use std::time::Duration;
+
+const PAGE_1: &str = include_str!("fixtures/repositories_page_1.json");
+const PAGE_2: &str = include_str!("fixtures/repositories_page_2.json");
+const PAYMENTS: &str = include_str!("fixtures/repositories_payments.json");
+const RENAMED: &str = include_str!("fixtures/repositories_renamed.json");
+const BAD_CREDENTIALS: &str = include_str!("fixtures/bad_credentials.json");
+
+fn serve_repositories(server: &MockServer) {
+    server.route(
+        Route::post("/graphql")
+            .body_contains("Y3Vyc29yOnYyOpHOAAAAAQ==")
+            .json(PAGE_2),
+    );
+    server.route(Route::post("/graphql").json(PAGE_1));
+}
+
+fn workflow(api_url: &str, database_url: &str) -> Workflow {
+    let http = Client::builder().backoff(Duration::from_millis(10));
+    Workflow::new(Secret::new("token"), api_url, database_url, http).unwrap()
+}
+
+#[test]
+fn refresh_follows_cursors_then_query_finds_repositories() {
+    let server = MockServer::start();
+    serve_repositories(&server);
+    let url = server.url();
+    let db = mock_server::temp_database("github-refresh-cursors");
+    let mut wf = workflow(&url, db.to_str().unwrap());
+
+    wf.refresh().unwrap();
+
+    let records = wf.query(None, "").unwrap();
+    let titles = records
+        .iter()
+        .map(|r| r.item.title.as_ref())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        titles,
+        ["acme/payments", "acme/ledger", "octocat/hello-world"]
+    );
+    let records = wf.query(None, "hello").unwrap();
+    assert_eq!(records[0].item.title, "octocat/hello-world");
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].header("Authorization"), Some("Bearer token"));
+    assert_eq!(
+        requests[0].header("User-Agent"),
+        Some("Alfred Github Workflow")
+    );
+}
+
+#[test]
+fn query_fuzzy_matches_what_the_index_does_not() {
+    let server = MockServer::start();
+    server.route(Route::post("/graphql").json(PAYMENTS));
+    let url = server.url();
+    let db = mock_server::temp_database("github-refresh-fuzzy");
+    let mut wf = workflow(&url, db.to_str().unwrap());
+
+    wf.refresh().unwrap();
+
+    let mut titles = |query: &str| {
+        wf.query(None, query)
+            .unwrap()
+            .into_iter()
+            .map(|record| record.item.title.into_owned())
+            .collect::<Vec<_>>()
+    };
+    assert!(titles("pymts").contains(&"acme/payments-api".to_owned()));
+    assert!(titles("ps").contains(&"acme/payments-api".to_owned()));
+    assert_eq!(titles("payments")[0], "acme/payments-api");
+    assert_eq!(titles("ledger"), ["acme/ledger"]);
+}
+
+#[test]
+fn refresh_caches_every_profile_and_query_filters_by_profile() {
+    let server = MockServer::start();
+    serve_repositories(&server);
+    let url = server.url();
+    let db = mock_server::temp_database("github-refresh-profiles");
+    let profiles = ["personal", "work"]
+        .into_iter()
+        .map(|name| Profile {
+            name: name.to_owned(),
+            api_key: Secret::new(format!("{name}-token")),
+            api_url: url.clone(),
+            http: Client::builder().backoff(Duration::from_millis(10)),
+        })
+        .collect();
+    let mut wf = Workflow::with_profiles(db.to_str().unwrap(), profiles).unwrap();
+
+    wf.refresh().unwrap();
+
+    assert_eq!(wf.query(None, "payments").unwrap().len(), 2);
+    let records = wf.query(None, "@work payments").unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].item.title, "acme/payments");
+    assert_eq!(records[0].item.subtitle.as_deref(), Some("work - payments"));
+    assert_eq!(records[0].item.uid.as_deref(), Some("work:acme/payments"));
+
+    let tokens = server
+        .requests()
+        .iter()
+        .filter_map(|r| r.header("Authorization").map(str::to_owned))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        tokens,
+        [
+            "Bearer personal-token",
+            "Bearer personal-token",
+            "Bearer work-token",
+            "Bearer work-token"
+        ]
+    );
+}
+
+#[test]
+fn refresh_tracks_renamed_repositories_and_new_profiles() {
+    let server = MockServer::start();
+    serve_repositories(&server);
+    let url = server.url();
+    let db = mock_server::temp_database("github-refresh-renamed");
+    workflow(&url, db.to_str().unwrap()).refresh().unwrap();
+
+    let renamed = MockServer::start();
+    renamed.route(Route::post("/graphql").json(RENAMED));
+    let url = renamed.url();
+    let mut wf = workflow(&url, db.to_str().unwrap());
+
+    // keyed by node id, so the rename is not a removal and an addition
+    let summary = wf.refresh().unwrap();
+    assert_eq!(summary.to_string(), "−1 repo, 1 repo renamed");
+    let since = Utc::now() - TimeDelta::days(7);
+    assert!(
+        wf.first_seen_since(since, &SearchQuery::default())
+            .unwrap()
+            .is_empty()
+    );
+
+    let profiles = ["", "work"]
+        .into_iter()
+        .map(|name| Profile {
+            name: name.to_owned(),
+            api_key: Secret::new("token"),
+            api_url: url.clone(),
+            http: Client::builder().backoff(Duration::from_millis(10)),
+        })
+        .collect();
+    let mut wf = Workflow::with_profiles(db.to_str().unwrap(), profiles).unwrap();
+
+    // the first refresh of a profile does not see its repositories appear
+    let summary = wf.refresh().unwrap();
+    assert_eq!(summary.to_string(), "+2 repos");
+    assert!(
+        wf.first_seen_since(since, &SearchQuery::default())
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn refresh_retries_server_errors_and_rate_limits() {
+    let server = MockServer::start();
+    server.route(Route::post("/graphql").status(502).times(1));
+    server.route(
+        Route::post("/graphql")
+            .status(403)
+            .header("X-RateLimit-Remaining", "0")
+            .header("X-RateLimit-Reset", "0")
+            .times(1),
+    );
+    serve_repositories(&server);
+    let url = server.url();
+    let db = mock_server::temp_database("github-refresh-retries");
+    let mut wf = workflow(&url, db.to_str().unwrap());
+
+    wf.refresh().unwrap();
+
+    assert_eq!(
+        wf.query(None, "payments").unwrap()[0].item.title,
+        "acme/payments"
+    );
+    assert_eq!(server.requests().len(), 4);
+}
+
+#[test]
+fn failed_refresh_keeps_the_cached_repositories() {
+    let server = MockServer::start();
+    serve_repositories(&server);
+    let url = server.url();
+    let db = mock_server::temp_database("github-refresh-failed");
+    workflow(&url, db.to_str().unwrap()).refresh().unwrap();
+
+    let rejecting = MockServer::start();
+    rejecting.route(Route::post("/graphql").status(401).json(BAD_CREDENTIALS));
+    let url = rejecting.url();
+    let mut wf = workflow(&url, db.to_str().unwrap());
+
+    let err = wf.refresh().unwrap_err();
+    assert_eq!(
+        err.to_failure(),
+        Failure::Unauthorized {
+            service: "GitHub",
+            status: 401
+        }
+    );
+    assert_eq!(
+        wf.query(None, "ledger").unwrap()[0].item.title,
+        "acme/ledger"
+    );
+}
+
+#[test]
+fn results_of_github_enterprise_profiles_are_allowed() {
+    let profile = |name: &str, api_url: &str| ApiConfig {
+        name: name.to_owned(),
+        api_key: Secret::new("token"),
+        http_timeout: Duration::from_secs(30),
+        https_proxy: None,
+        no_proxy: None,
+        ca_bundle: None,
+        api_url: api_url.to_owned(),
+    };
+    let config = Config {
+        profiles: vec![
+            profile("work", "https://github.example.com/api"),
+            profile("oss", "https://api.github.com"),
+        ],
+        cache: CacheConfig {
+            database_url: String::new(),
+            open_command: None,
+            cache_ttl: Duration::ZERO,
+            stale_after: Duration::ZERO,
+        },
+    };
+
+    assert_eq!(
+        Workflow::allowed_hosts(&config),
+        ["github.com", "github.example.com"]
+    );
+}
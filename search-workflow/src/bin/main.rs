@@ -0,0 +1,110 @@
+use alfred_workflow::config::WorkflowConfig;
+use alfred_workflow::failure::Failure;
+use alfred_workflow::frecency;
+use alfred_workflow::logging;
+use alfred_workflow::output::{self, Format};
+use alfred_workflow::response::ScriptFilterResponse;
+use alfred_workflow::runner;
+use anyhow::Error;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use search_workflow_lib::config::Config;
+use search_workflow_lib::errors::Error as WorkflowError;
+use search_workflow_lib::workflow::Workflow;
+use std::io;
+
+#[derive(Parser)]
+#[command(version, about, propagate_version = true)]
+struct Cli {
+    /// Searches the caches of every workflow
+    #[arg(allow_hyphen_values = true)]
+    query: Option<Vec<String>>,
+
+    /// How results are printed: alfred, json, table or tsv; Alfred items when run by Alfred, a
+    /// table in a terminal and tsv otherwise
+    #[arg(long, global = true)]
+    format: Option<Format>,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Opens the workflow configuration, results are opened by their own workflow
+    Open {
+        url: String,
+
+        /// Prints the command that would open the URL instead of running it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Workflow configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Prints the effective configuration and any problems with it
+    Check,
+}
+
+fn main() -> Result<(), Error> {
+    logging::init(Config::NAME);
+    let cli = Cli::from_arg_matches(&runner::get_matches(Cli::command()))?;
+    let script_filter = cli.command.is_none();
+    let format = cli.format.unwrap_or_else(Format::detect);
+
+    run(cli, format).or_else(|err| {
+        let failure = Failure::classify::<WorkflowError>(&err);
+        if script_filter && format != Format::Alfred {
+            Err(err)
+        } else if script_filter {
+            // there is nothing to refresh, each workflow refreshes its own cache
+            ScriptFilterResponse::new()
+                .item(failure.into_item(""))
+                .write(io::stdout())?;
+            Ok(())
+        } else {
+            println!("{failure}");
+            Err(err)
+        }
+    })
+}
+
+fn run(cli: Cli, format: Format) -> Result<(), Error> {
+    if let Some(Commands::Config {
+        command: ConfigCommands::Check,
+    }) = &cli.command
+    {
+        let (config, loader) = Config::check()?;
+        print!("{loader}");
+        loader.validate()?;
+        if config.caches.is_empty() {
+            return Err(WorkflowError::no_caches().into());
+        }
+        return Ok(());
+    }
+
+    let wf = Workflow::new(Config::load()?);
+    match cli.command {
+        Some(Commands::Open { url, dry_run }) => wf.open(&url, dry_run)?,
+        Some(Commands::Config { .. }) => {}
+        None => {
+            let query = cli.query.unwrap_or_default().join(" ");
+            let query = query.trim();
+            let records = wf.search(query)?;
+            if format == Format::Alfred {
+                ScriptFilterResponse::new()
+                    .items(records.into_iter().map(|record| record.item))
+                    .variable(frecency::QUERY_VARIABLE, query)
+                    .write(io::stdout())?;
+            } else {
+                output::write(format, &records, io::stdout())?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use crate::sources::{SOURCES, Source};
+use alfred_workflow::config::{Loader, WorkflowConfig};
+
+/// Where a source's cache is kept.
+#[derive(Debug)]
+pub struct Cache {
+    pub source: &'static Source,
+    pub database_url: String,
+}
+
+/// The workflow's configuration, see `alfred_workflow::config` for where it is read from.
+#[derive(Debug)]
+pub struct Config {
+    /// The caches of the sources whose variable is set.
+    pub caches: Vec<Cache>,
+    pub open_command: Option<String>,
+}
+
+impl WorkflowConfig for Config {
+    const NAME: &'static str = "search";
+
+    fn read(loader: &mut Loader) -> Self {
+        // only the workflows know where Alfred keeps their data, so each cache must be set
+        let caches = SOURCES
+            .iter()
+            .filter_map(|source| {
+                Some(Cache {
+                    source,
+                    database_url: loader.optional(source.variable)?,
+                })
+            })
+            .collect();
+        Self {
+            caches,
+            open_command: loader.optional("OPEN_COMMAND"),
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use crate::sources::SOURCES;
+use alfred_workflow::failure::{Failure, ToFailure};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error(transparent)]
+    SQLite(#[from] rusqlite::Error),
+
+    #[error(transparent)]
+    AlfredWorkflow(#[from] alfred_workflow::errors::Error),
+
+    #[error("no workflow caches configured, set {} to the DATABASE_URL of the workflows searched", .0.join(", "))]
+    NoCaches(Vec<&'static str>),
+
+    #[error("no workflow caches found at {}, refresh the workflows first", .0.join(", "))]
+    CachesNotFound(Vec<String>),
+}
+
+impl Error {
+    /// No cache is configured, naming the variable of every source.
+    #[must_use]
+    pub fn no_caches() -> Self {
+        Self::NoCaches(SOURCES.iter().map(|source| source.variable).collect())
+    }
+}
+
+impl ToFailure for Error {
+    fn to_failure(&self) -> Failure {
+        match self {
+            Self::AlfredWorkflow(e) => e.to_failure(),
+            Self::NoCaches(variables) => Failure::MissingVariable(variables.join(", ")),
+            Self::SQLite(_) | Self::CachesNotFound(_) => Failure::Other(self.to_string()),
+        }
+    }
+}
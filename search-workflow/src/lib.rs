@@ -0,0 +1,4 @@
+pub mod config;
+pub mod errors;
+pub mod sources;
+pub mod workflow;
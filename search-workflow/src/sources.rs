@@ -0,0 +1,114 @@
+//! The workflows whose caches are searched, and how to read each of their tables.
+//!
+use alfred_workflow::search::SearchFields;
+
+/// A workflow whose cache is searched.
+#[derive(Debug)]
+pub struct Source {
+    /// Identifies the source in the open action, eg. `github`.
+    pub name: &'static str,
+    /// Variable holding the path of the workflow's cache, its `DATABASE_URL`.
+    pub variable: &'static str,
+    /// Icon shown next to the source's results, relative to the workflow's directory.
+    pub icon: &'static str,
+    pub resources: &'static [Resource],
+}
+
+/// A cached table of a source.
+#[derive(Debug)]
+pub struct Resource {
+    pub table: &'static str,
+    /// Describes the results in their subtitle, eg. `GitHub repository`.
+    pub label: &'static str,
+    /// Selects the `key` the source's search index refers to every row by, its `profile`,
+    /// `title`, matched `text`, `detail` shown in the subtitle, `url` and `modified` time.
+    pub select: &'static str,
+    /// Search fields, referring to the selected columns.
+    pub fields: SearchFields<'static>,
+}
+
+pub const SOURCES: &[Source] = &[
+    Source {
+        name: "github",
+        variable: "GITHUB_DATABASE_URL",
+        icon: "github.png",
+        resources: &[Resource {
+            table: "repositories",
+            label: "GitHub repository",
+            select: "SELECT rowid AS key, profile, name_with_owner AS title, name AS text, '' AS detail, url, pushed_at AS modified
+                FROM repositories",
+            fields: SearchFields {
+                default: &["text"],
+                qualifiers: &[
+                    ("repo", "text"),
+                    ("owner", "substr(title, 1, instr(title, '/') - 1)"),
+                ],
+                profile: Some("profile"),
+            },
+        }],
+    },
+    Source {
+        name: "buildkite",
+        variable: "BUILDKITE_DATABASE_URL",
+        icon: "buildkite.png",
+        resources: &[Resource {
+            table: "pipelines",
+            label: "Buildkite pipeline",
+            select: "SELECT rowid AS key, profile, unique_name AS title, name AS text, '' AS detail, url, NULL AS modified
+                FROM pipelines",
+            fields: SearchFields {
+                default: &["text"],
+                qualifiers: &[
+                    ("pipeline", "text"),
+                    ("org", "substr(title, 1, instr(title, '/') - 1)"),
+                ],
+                profile: Some("profile"),
+            },
+        }],
+    },
+    Source {
+        name: "datadog",
+        variable: "DATADOG_DATABASE_URL",
+        icon: "datadog.png",
+        resources: &[
+            Resource {
+                table: "timeboards",
+                label: "Datadog timeboard",
+                select: "SELECT rowid AS key, profile, title, title AS text, description AS detail, url, modified
+                    FROM timeboards",
+                fields: BOARD_FIELDS,
+            },
+            Resource {
+                table: "screenboards",
+                label: "Datadog screenboard",
+                select: "SELECT rowid AS key, profile, title, title AS text, description AS detail, url, modified
+                    FROM screenboards",
+                fields: BOARD_FIELDS,
+            },
+            Resource {
+                table: "monitors",
+                label: "Datadog monitor",
+                select: "SELECT m.rowid AS key, m.profile, m.name AS title, m.name AS text, '' AS detail, m.url, m.modified,
+                    coalesce((SELECT group_concat(t.name, ' ') FROM monitor_tags t WHERE t.profile = m.profile AND t.id = m.id), '') AS tags
+                    FROM monitors m",
+                fields: SearchFields {
+                    default: &["text"],
+                    qualifiers: &[("name", "text"), ("tag", "tags")],
+                    profile: Some("profile"),
+                },
+            },
+        ],
+    },
+];
+
+const BOARD_FIELDS: SearchFields = SearchFields {
+    default: &["text"],
+    qualifiers: &[("title", "text"), ("desc", "detail")],
+    profile: Some("profile"),
+};
+
+/// Returns the source with the given name.
+#[must_use]
+pub fn find(name: &str) -> Option<&'static Source> {
+    SOURCES.iter().find(|source| source.name == name)
+}
@@ -0,0 +1,190 @@
+use crate::config::{Cache, Config};
+use crate::errors::Error;
+use crate::sources::{Resource, Source};
+use alfred::Item;
+use alfred_workflow::opener::OpenOptions;
+use alfred_workflow::output::Record;
+use alfred_workflow::search::SearchQuery;
+use alfred_workflow::{frecency, fts, profile};
+use log::{debug, warn};
+use rusqlite::{Connection, ToSql};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::path::Path;
+
+/// Results shown, across every source.
+const LIMIT: usize = 20;
+
+/// Alfred variable naming the workflow a result came from, eg. `github`. Enter calls that
+/// workflow's `open` External Trigger with the result's argument, `open <url>`, so it opens
+/// the result as its own and ranks it higher; this workflow never writes to its cache.
+pub const SOURCE_VARIABLE: &str = "source";
+
+/// A cached row of any source.
+#[derive(Serialize)]
+struct Row {
+    source: &'static str,
+    resource: &'static str,
+    profile: String,
+    title: String,
+    /// The text matched, the title and what the source also searches.
+    #[serde(skip)]
+    text: String,
+    detail: String,
+    url: String,
+}
+
+pub struct Workflow {
+    caches: Vec<Cache>,
+    open_command: Option<String>,
+}
+
+impl Workflow {
+    #[inline]
+    #[must_use]
+    pub fn new(config: Config) -> Self {
+        Self {
+            caches: config.caches,
+            open_command: config.open_command,
+        }
+    }
+
+    /// Searches every configured source's cache, opened read-only, ranking the results of all
+    /// of them together. Sources whose cache does not exist yet are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if no cache is configured or exists, or one cannot be read.
+    pub fn query(&self, text: &str) -> Result<Vec<Item<'static>>, Error> {
+        Ok(self
+            .search(text)?
+            .into_iter()
+            .map(|record| record.item)
+            .collect())
+    }
+
+    /// Searches every source's cache like `query`, returning the rows found along with the
+    /// items showing them.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if no cache is configured or exists, or one cannot be read.
+    pub fn search(&self, text: &str) -> Result<Vec<Record>, Error> {
+        let query = SearchQuery::parse(text);
+        if self.caches.is_empty() {
+            return Err(Error::no_caches());
+        }
+        let mut scored = Vec::new();
+        let mut searched = false;
+
+        for cache in &self.caches {
+            if !Path::new(&cache.database_url).exists() {
+                debug!("no {} cache at {}", cache.source.name, cache.database_url);
+                continue;
+            }
+            searched = true;
+            let conn = alfred_workflow::open_database_read_only(&cache.database_url)?;
+            let selections = frecency::scores(&conn, text).unwrap_or_else(|e| {
+                warn!("not ranking {} by frecency: {e}", cache.source.name);
+                Default::default()
+            });
+            for resource in cache.source.resources {
+                for row in find(&conn, cache.source, resource, &query)? {
+                    let score = query.score(&row.text).unwrap_or(0)
+                        + selections.get(&row.url).copied().unwrap_or_default();
+                    scored.push((score, cache.source, resource, row));
+                }
+            }
+        }
+
+        if !searched {
+            return Err(Error::CachesNotFound(
+                self.caches.iter().map(|c| c.database_url.clone()).collect(),
+            ));
+        }
+        // stable, so results that score the same stay in source order, most recent first
+        scored.sort_by_key(|(score, ..)| Reverse(*score));
+        // only the results shown are turned into items
+        scored
+            .into_iter()
+            .take(LIMIT)
+            .map(|(_, source, resource, row)| {
+                Ok(Record::new(row, |row| item(source, resource, row))?)
+            })
+            .collect()
+    }
+
+    /// Opens the workflow configuration in Alfred Preferences, eg. from a failure's item.
+    /// Results are opened by their source's workflow instead, see `SOURCE_VARIABLE`.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the URL is not an Alfred Preferences link or cannot be opened.
+    pub fn open(&self, url: &str, dry_run: bool) -> Result<(), Error> {
+        let options = OpenOptions::new(&[])
+            .command(self.open_command.as_deref())
+            .dry_run(dry_run);
+        alfred_workflow::open_url(url, &options)?;
+        Ok(())
+    }
+}
+
+fn find(
+    conn: &Connection,
+    source: &Source,
+    resource: &Resource,
+    query: &SearchQuery,
+) -> Result<Vec<Row>, Error> {
+    let search = fts::compile(
+        query,
+        &[resource.table],
+        "fts.key = r.key",
+        &resource.fields,
+    );
+    let params = search
+        .params
+        .iter()
+        .map(|p| p as &dyn ToSql)
+        .collect::<Vec<_>>();
+
+    conn.prepare(&format!(
+        "SELECT r.profile, r.title, r.text, r.detail, r.url FROM ({}) r {} WHERE {} ORDER BY {} LIMIT {}",
+        resource.select,
+        search.join,
+        search.clause,
+        search.order_by("r.modified DESC"),
+        fts::CANDIDATES
+    ))?
+    .query_map(&*params, |row| {
+        Ok(Row {
+            source: source.name,
+            resource: resource.table,
+            profile: row.get(0)?,
+            title: row.get(1)?,
+            text: row.get(2)?,
+            detail: row.get(3)?,
+            url: row.get(4)?,
+        })
+    })?
+    .map(|r| Ok(r?))
+    .collect()
+}
+
+fn item(source: &Source, resource: &Resource, row: Row) -> Item<'static> {
+    let subtitle = if row.detail.is_empty() {
+        resource.label.to_owned()
+    } else {
+        format!("{} - {}", resource.label, row.detail)
+    };
+    alfred::ItemBuilder::new(row.title.clone())
+        .uid(profile::uid(&row.profile, &row.url))
+        .subtitle(profile::subtitle(&row.profile, &subtitle))
+        .icon_path(source.icon)
+        .autocomplete(row.text)
+        .arg(format!("open {}", row.url))
+        .variable(SOURCE_VARIABLE, source.name)
+        .quicklook_url(row.url.clone())
+        .text_copy(row.url)
+        .text_large_type(row.title)
+        .into_item()
+}
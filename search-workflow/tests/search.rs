@@ -0,0 +1,216 @@
+use alfred::Item;
+use alfred_workflow::failure::{Failure, ToFailure};
+use alfred_workflow::fts;
+use alfred_workflow::http::Client;
+use alfred_workflow::mock_server;
+use alfred_workflow::secret::Secret;
+use alfred_workflow::workflow::Workflow as _;
+use search_workflow_lib::config::{Cache, Config};
+use search_workflow_lib::sources::{self, SOURCES};
+use search_workflow_lib::workflow::{SOURCE_VARIABLE, Workflow};
+use std::path::PathBuf;
+
+/// Creates the GitHub, Buildkite and Datadog caches using each workflow's own schema.
+fn caches(name: &str) -> Vec<PathBuf> {
+    let github = mock_server::temp_database(&format!("{name}-github"));
+    let wf = github_workflow_lib::workflow::Workflow::new(
+        Secret::new("token"),
+        "https://api.github.com",
+        github.to_str().unwrap(),
+        Client::builder(),
+    )
+    .unwrap();
+    wf.connection()
+        .execute_batch(
+            "INSERT INTO repositories (id, name_with_owner, name, url, pushed_at) VALUES
+                ('R_1', 'acme/payments-api', 'payments-api', 'https://github.com/acme/payments-api', '2024-03-01T00:00:00Z'),
+                ('R_2', 'acme/website', 'website', 'https://github.com/acme/website', '2024-02-01T00:00:00Z');",
+        )
+        .unwrap();
+    index(&wf);
+
+    let buildkite = mock_server::temp_database(&format!("{name}-buildkite"));
+    let wf = buildkite_workflow_lib::workflow::Workflow::new(
+        Secret::new("token"),
+        "https://api.buildkite.com/v2",
+        buildkite.to_str().unwrap(),
+        Client::builder(),
+    )
+    .unwrap();
+    wf.connection()
+        .execute_batch(
+            "INSERT INTO pipelines (unique_name, name, url) VALUES
+                ('acme/payments-deploy', 'payments-deploy', 'https://buildkite.com/acme/payments-deploy');",
+        )
+        .unwrap();
+    index(&wf);
+
+    let datadog = mock_server::temp_database(&format!("{name}-datadog"));
+    let wf = datadog_workflow_lib::workflow::Workflow::new(
+        Secret::new("key"),
+        Secret::new("app-key"),
+        datadog.to_str().unwrap(),
+        "https://api.datadoghq.com/api",
+        "acme",
+        Client::builder(),
+    )
+    .unwrap();
+    wf.connection()
+        .execute_batch(
+            "INSERT INTO timeboards (id, title, description, url, modified) VALUES
+                ('abc', 'Payments Overview', 'checkout health', 'https://acme.datadoghq.com/dash/abc', '2024-03-01T00:00:00Z');
+            INSERT INTO monitors (id, name, url, modified) VALUES
+                (1, 'Payments API p99 latency', 'https://acme.datadoghq.com/monitors/1', '2024-03-01T00:00:00Z');
+            INSERT INTO monitor_tags (id, name) VALUES (1, 'team:checkout');",
+        )
+        .unwrap();
+    index(&wf);
+
+    vec![github, buildkite, datadog]
+}
+
+/// Rebuilds the full-text index of the rows inserted, as the workflow's refreshes do.
+fn index<W: alfred_workflow::workflow::Workflow>(wf: &W) {
+    fts::rebuild(wf.connection(), W::SEARCH_INDEX).unwrap();
+}
+
+fn workflow(paths: &[PathBuf]) -> Workflow {
+    let caches = SOURCES
+        .iter()
+        .zip(paths)
+        .map(|(source, path)| Cache {
+            source,
+            database_url: path.to_string_lossy().into_owned(),
+        })
+        .collect();
+    Workflow::new(Config {
+        caches,
+        // succeeds without opening anything
+        open_command: Some("true".to_owned()),
+    })
+}
+
+fn titles(items: &[Item]) -> Vec<String> {
+    items.iter().map(|item| item.title.to_string()).collect()
+}
+
+#[test]
+fn query_ranks_results_of_every_source_together() {
+    let wf = workflow(&caches("search-every-source"));
+
+    let items = wf.query("payments").unwrap();
+    assert_eq!(
+        titles(&items),
+        [
+            "acme/payments-api",
+            "acme/payments-deploy",
+            "Payments Overview",
+            "Payments API p99 latency"
+        ]
+    );
+    assert_eq!(
+        items[2].subtitle.as_deref(),
+        Some("Datadog timeboard - checkout health")
+    );
+    assert!(items[2].icon.is_some());
+    // opened by the GitHub workflow
+    assert_eq!(
+        items[0].arg.as_deref(),
+        Some("open https://github.com/acme/payments-api")
+    );
+    assert_eq!(items[0].variables[SOURCE_VARIABLE], "github");
+
+    assert_eq!(
+        titles(&wf.query("tag:team:checkout").unwrap()),
+        ["Payments API p99 latency"]
+    );
+    assert_eq!(titles(&wf.query("website").unwrap()), ["acme/website"]);
+
+    let records = wf.search("payments overview").unwrap();
+    assert_eq!(records[0].row["source"], "datadog");
+    assert_eq!(records[0].row["resource"], "timeboards");
+    assert_eq!(records[0].row["title"], "Payments Overview");
+    assert!(records[0].row.get("text").is_none());
+}
+
+#[test]
+fn query_matches_what_each_source_workflow_does() {
+    let wf = workflow(&caches("search-like-sources"));
+
+    // the owners, descriptions and tags each workflow indexes
+    assert_eq!(
+        titles(&wf.query("acme").unwrap()),
+        ["acme/website", "acme/payments-api", "acme/payments-deploy"]
+    );
+    assert_eq!(
+        titles(&wf.query("checkout").unwrap()),
+        ["Payments Overview", "Payments API p99 latency"]
+    );
+    // and still fuzzy matching their titles
+    assert_eq!(
+        titles(&wf.query("pymtsdpl").unwrap()),
+        ["acme/payments-deploy"]
+    );
+}
+
+#[test]
+fn results_opened_by_their_source_workflow_rank_first() {
+    let paths = caches("search-open");
+    let buildkite = buildkite_workflow_lib::workflow::Workflow::new(
+        Secret::new("token"),
+        "https://api.buildkite.com/v2",
+        paths[1].to_str().unwrap(),
+        Client::builder(),
+    )
+    .unwrap();
+    buildkite
+        .record_open("https://buildkite.com/acme/payments-deploy", "pay")
+        .unwrap();
+    let wf = workflow(&paths);
+
+    assert_eq!(titles(&wf.query("pay").unwrap())[0], "acme/payments-deploy");
+}
+
+#[test]
+fn open_only_opens_the_workflow_configuration() {
+    let wf = workflow(&caches("search-open-configuration"));
+
+    assert!(
+        wf.open("alfredpreferences://navigateto/workflows", true)
+            .is_ok()
+    );
+    // results are opened by their own workflow
+    assert!(wf.open("https://github.com/acme/website", true).is_err());
+    assert!(sources::find("datadog").is_some());
+}
+
+#[test]
+fn missing_caches_are_skipped_unless_every_one_is() {
+    let mut paths = caches("search-missing");
+    paths[0] = mock_server::temp_database("search-missing-none");
+    let wf = workflow(&paths);
+    assert_eq!(titles(&wf.query("website").unwrap()), Vec::<String>::new());
+
+    let missing = mock_server::temp_database("search-missing-all");
+    let wf = workflow(&[missing.clone(), missing.clone(), missing.clone()]);
+    let err = wf.query("").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "no workflow caches found at {0}, {0}, {0}, refresh the workflows first",
+            missing.display()
+        )
+    );
+}
+
+#[test]
+fn caches_must_be_configured() {
+    let wf = workflow(&[]);
+    let err = wf.query("").unwrap_err();
+    assert_eq!(
+        err.to_failure(),
+        Failure::MissingVariable(
+            "GITHUB_DATABASE_URL, BUILDKITE_DATABASE_URL, DATADOG_DATABASE_URL".to_owned()
+        )
+    );
+}